@@ -0,0 +1,86 @@
+use crate::{error::RuntimeError, token::Token, value::Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    /// Looks up `name` in the scope exactly `distance` hops up the chain
+    pub fn get_at(&self, distance: usize, name: &str) -> Value {
+        if distance == 0 {
+            return self.values[name].clone();
+        }
+
+        self.enclosing
+            .as_ref()
+            .expect("no enclosing environment at that distance")
+            .borrow()
+            .get_at(distance - 1, name)
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value);
+            return;
+        }
+
+        self.enclosing
+            .as_ref()
+            .expect("no enclosing environment at that distance")
+            .borrow_mut()
+            .assign_at(distance - 1, name, value)
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    fn undefined(name: &Token) -> RuntimeError {
+        RuntimeError::new(
+            name.clone(),
+            &format!("Undefined variable '{}'.", name.lexeme),
+        )
+    }
+}
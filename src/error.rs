@@ -1,12 +1,115 @@
+use crate::parser::{self, ParseError, ParseErrorKind};
+use crate::scanner::ScanError;
 use crate::token::{Token, TokenType};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static HAD_ERROR: AtomicBool = AtomicBool::new(false);
+static HAD_RUNTIME_ERROR: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(token: Token, message: &str) -> Self {
+        Self {
+            token,
+            message: message.into(),
+        }
+    }
+}
 
 pub fn error(token: Token, message: &str) {
     match token.token_type {
-        TokenType::EOF => report(token.line, " at end", message),
-        _ => report(token.line, &format!(" at '{}'", token.lexeme), message),
+        TokenType::EOF => report(token.line, token.column, " at end", message),
+        _ => report(
+            token.line,
+            token.column,
+            &format!(" at '{}'", token.lexeme),
+            message,
+        ),
+    }
+}
+
+pub fn had_error() -> bool {
+    HAD_ERROR.load(Ordering::Relaxed)
+}
+
+/// Forgets any reported error so the REPL can carry on after a bad line
+pub fn reset_error() {
+    HAD_ERROR.store(false, Ordering::Relaxed);
+}
+
+pub fn parse_error(e: &ParseError) {
+    match expected_list(e) {
+        Some(expected) => error(
+            e.token.clone(),
+            &format!("{} (expected {})", e.message, expected),
+        ),
+        None => error(e.token.clone(), &e.message),
+    }
+}
+
+/// Spells out the tokens that would have been accepted instead of `e.token`,
+/// if there are few enough to be worth reading. Infix operators are lumped
+/// together, and a lone token is left out since the message names it.
+fn expected_list(e: &ParseError) -> Option<String> {
+    if e.kind == ParseErrorKind::ExpectedExpression {
+        return None;
+    }
+
+    let mut names: Vec<&str> = e
+        .expected
+        .iter()
+        .filter(|&&t| !parser::is_operator(t))
+        .map(|t| t.describe())
+        .collect();
+    if e.expected.iter().any(|&t| parser::is_operator(t)) {
+        names.push("an operator");
+    }
+
+    match names.as_slice() {
+        [] | [_] => None,
+        names if names.len() > 4 => None,
+        [names @ .., last] => Some(format!("{} or {}", names.join(", "), last)),
     }
 }
 
-fn report(line: usize, where_: &str, message: &str) {
-    eprintln!("[line {}] Error{}: {}", line, where_, message);
+/// Reports a lexical error, which isn't tied to any token
+pub fn scan_error(error: &ScanError) {
+    report(error.line, error.column, "", &error.message);
+}
+
+pub fn runtime_error(error: &RuntimeError) {
+    eprintln!(
+        "{}\n[line {}:{}]",
+        error.message, error.token.line, error.token.column
+    );
+    HAD_RUNTIME_ERROR.store(true, Ordering::Relaxed);
+}
+
+pub fn had_runtime_error() -> bool {
+    HAD_RUNTIME_ERROR.load(Ordering::Relaxed)
+}
+
+fn report(line: usize, column: usize, where_: &str, message: &str) {
+    let report = format!("[line {}:{}] Error{}: {}", line, column, where_, message);
+    #[cfg(test)]
+    REPORTED.with(|reported| reported.borrow_mut().push(report.clone()));
+    eprintln!("{}", report);
+    HAD_ERROR.store(true, Ordering::Relaxed);
+}
+
+// Tests run on several threads at once, so each keeps its own reports
+#[cfg(test)]
+thread_local! {
+    static REPORTED: std::cell::RefCell<Vec<String>> = Default::default();
+}
+
+/// Hands over every error reported on this thread since the last call
+#[cfg(test)]
+pub fn take_reported() -> Vec<String> {
+    REPORTED.with(|reported| reported.take())
 }
@@ -1,69 +1,672 @@
-use crate::{parser::Expr, token::Literal, token::Token, token::TokenType};
+use crate::{
+    callable::{LoxCallable, LoxFunction, NativeFunction},
+    class::{LoxClass, LoxInstance},
+    environment::Environment,
+    error::{self, RuntimeError},
+    parser::{Expr, ExprId, FunctionDecl, Stmt},
+    token::{Literal, Token, TokenType},
+    value::Value,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub struct Interpreter {}
+/// Unwinds the interpreter out of the statements being executed, either to
+/// report an error or to hand a `return` value back to the caller
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(e: RuntimeError) -> Self {
+        Unwind::Error(e)
+    }
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    locals: HashMap<ExprId, usize>,
+    // what the script was run with, for the `args` native
+    args: Vec<String>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Interpreter {
-    pub fn visit_grouping_expr(&self, expr: Expr) -> Literal {
-        match expr {
-            Expr::Grouping(_) => self.evaluate(expr),
-            _ => panic!("Expr must be Expr::Grouping variant"),
+    pub fn new() -> Self {
+        Self::with_args(Vec::new())
+    }
+
+    /// An interpreter whose `args()` native returns `args`
+    pub fn with_args(args: Vec<String>) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+
+        globals.borrow_mut().define(
+            "clock",
+            Value::Native(Rc::new(NativeFunction {
+                name: "clock",
+                arity: 0,
+                function: |_, _| {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                    Value::Number(now.as_secs_f64())
+                },
+            })),
+        );
+
+        globals.borrow_mut().define(
+            "args",
+            Value::Native(Rc::new(NativeFunction {
+                name: "args",
+                arity: 0,
+                function: |interpreter, _| {
+                    let args = interpreter
+                        .args
+                        .iter()
+                        .map(|arg| Value::String(arg.as_str().into()))
+                        .collect();
+                    Value::List(args)
+                },
+            })),
+        );
+
+        Self {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            args,
         }
     }
 
-    pub fn visit_unary_expr(&self, expr: Expr) -> Literal {
-        match expr {
-            Expr::Unary { operator, right } => {
-                let right = self.evaluate(*right);
-                match operator.token_type {
-                    TokenType::Minus => return -right,
-                    TokenType::Bang => return !Self::is_truthy(right),
-                    _ => {}
-                }
+    /// Records that the variable used by expression `id` lives `depth` scopes
+    /// out from where it is used. Unrecorded variables are globals.
+    pub fn resolve(&mut self, id: ExprId, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    #[cfg(test)]
+    pub fn resolved_depth(&self, id: ExprId) -> Option<usize> {
+        self.locals.get(&id).copied()
+    }
 
-                unreachable!()
+    pub fn interpret(&mut self, statements: Vec<Stmt>) {
+        for statement in &statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(e)) => {
+                    error::runtime_error(&e);
+                    return;
+                }
+                Err(Unwind::Return(_)) => return,
             }
-            _ => panic!("Expr must be Expr::Unary variant"),
         }
     }
 
-    // TODO restrict the types
-    pub fn visit_binary_expr(&self, expr: Expr) -> Literal {
+    /// Evaluates a single expression and prints its value, for the REPL
+    pub fn interpret_expression(&mut self, expr: &Expr) {
+        match self.evaluate(expr) {
+            Ok(value) => println!("{}", value),
+            Err(e) => error::runtime_error(&e),
+        }
+    }
+
+    /// Matches on the node itself rather than going through a `Visitor`,
+    /// whose defaults would let a forgotten node kind quietly do nothing.
+    /// `execute` does the same for statements.
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
             Expr::Binary {
                 left,
                 operator,
                 right,
-            } => {
-                let left = self.evaluate(*left);
-                let right = self.evaluate(*right);
-                match operator.token_type {
-                    TokenType::Plus => left + right,
-                    TokenType::Minus => left - right,
-                    TokenType::Slash => left / right,
-                    TokenType::Star => left * right,
-                    TokenType::Greater => Literal::Boolean(left > right),
-                    TokenType::GreaterEqual => Literal::Boolean(left >= right),
-                    TokenType::Less => Literal::Boolean(left < right),
-                    TokenType::LessEqual => Literal::Boolean(left <= right),
-                    TokenType::BangEqual => Literal::Boolean(left != right),
-                    TokenType::EqualEqual => Literal::Boolean(left == right),
-                    _ => unreachable!(),
+            } => self.visit_binary_expr(left, operator, right),
+            Expr::Unary { operator, right } => self.visit_unary_expr(operator, right),
+            Expr::Literal { value, .. } => self.visit_literal_expr(value),
+            Expr::Grouping { expression, .. } => self.visit_grouping_expr(expression),
+            Expr::Variable { id, name } => self.visit_variable_expr(*id, name),
+            Expr::Assign { id, name, value } => self.visit_assign_expr(*id, name, value),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => self.visit_logical_expr(left, operator, right),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.visit_call_expr(callee, paren, arguments),
+            Expr::Get { object, name } => self.visit_get_expr(object, name),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => self.visit_set_expr(object, name, value),
+            Expr::This { id, keyword } => self.visit_this_expr(*id, keyword),
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => self.visit_super_expr(*id, keyword, method),
+            Expr::Interpolation { parts, .. } => self.visit_interpolation_expr(parts),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.visit_ternary_expr(condition, then_branch, else_branch),
+        }
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        match stmt {
+            Stmt::Block(statements) => self.visit_block_stmt(statements),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+            } => self.visit_class_stmt(
+                name,
+                superclass.as_ref(),
+                methods,
+                doc.as_deref().map(String::as_str),
+            ),
+            Stmt::Expression(expression) => self.visit_expression_stmt(expression),
+            Stmt::Function(declaration) => self.visit_function_stmt(declaration),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.visit_if_stmt(condition, then_branch, else_branch.as_deref()),
+            Stmt::Print(expression) => self.visit_print_stmt(expression),
+            Stmt::Return { keyword, value } => self.visit_return_stmt(keyword, value.as_ref()),
+            Stmt::Var {
+                name,
+                initializer,
+                doc,
+            } => self.visit_var_stmt(
+                name,
+                initializer.as_ref(),
+                doc.as_deref().map(String::as_str),
+            ),
+            Stmt::While { condition, body } => self.visit_while_stmt(condition, body),
+        }
+    }
+
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+
+        self.environment = previous;
+        result
+    }
+
+    fn look_up_variable(&self, name: &Token, id: ExprId) -> Result<Value, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(&distance) => Ok(self.environment.borrow().get_at(distance, &name.lexeme)),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn check_number_operands(
+        operator: &Token,
+        left: &Value,
+        right: &Value,
+    ) -> Result<(f64, f64), RuntimeError> {
+        match (left, right) {
+            (Value::Number(x), Value::Number(y)) => Ok((*x, *y)),
+            _ => Err(RuntimeError::new(
+                operator.clone(),
+                "Operands must be numbers.",
+            )),
+        }
+    }
+
+    fn is_truthy(literal: &Value) -> bool {
+        match literal {
+            Value::Nil => false,
+            Value::Boolean(b) => *b,
+            _ => true,
+        }
+    }
+}
+
+// one method per statement kind, called from `execute`
+impl Interpreter {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<(), Unwind> {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(statements, Rc::new(RefCell::new(environment)))
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+        _doc: Option<&str>,
+    ) -> Result<(), Unwind> {
+        let superclass = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let token = match expr {
+                        Expr::Variable { name, .. } => name.clone(),
+                        _ => name.clone(),
+                    };
+                    return Err(RuntimeError::new(token, "Superclass must be a class.").into());
                 }
+            },
+            None => None,
+        };
+
+        self.environment
+            .borrow_mut()
+            .define(&name.lexeme, Value::Nil);
+
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(Rc::clone(&enclosing));
+            environment.define("super", Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let methods = methods
+            .iter()
+            .map(|method| {
+                let function = LoxFunction::new(
+                    Rc::clone(method),
+                    Rc::clone(&self.environment),
+                    method.name.lexeme == "init",
+                );
+                (method.name.lexeme.clone(), Rc::new(function))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let class = LoxClass::new(name.lexeme.clone(), superclass, methods);
+
+        self.environment = enclosing;
+        self.environment
+            .borrow_mut()
+            .assign(name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<(), Unwind> {
+        self.evaluate(expression)?;
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, declaration: &Rc<FunctionDecl>) -> Result<(), Unwind> {
+        let function =
+            LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+        self.environment
+            .borrow_mut()
+            .define(&declaration.name.lexeme, Value::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Result<(), Unwind> {
+        if Self::is_truthy(&self.evaluate(condition)?) {
+            self.execute(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) -> Result<(), Unwind> {
+        let value = self.evaluate(expression)?;
+        println!("{}", value);
+        Ok(())
+    }
+
+    fn visit_return_stmt(&mut self, _keyword: &Token, value: Option<&Expr>) -> Result<(), Unwind> {
+        let value = match value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expr>,
+        _doc: Option<&str>,
+    ) -> Result<(), Unwind> {
+        let value = match initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment.borrow_mut().define(&name.lexeme, value);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, condition: &Expr, body: &Stmt) -> Result<(), Unwind> {
+        while Self::is_truthy(&self.evaluate(condition)?) {
+            self.execute(body)?;
+        }
+        Ok(())
+    }
+}
+
+// one method per expression kind, called from `evaluate`
+impl Interpreter {
+    fn visit_binary_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(left)?;
+        let right = self.evaluate(right)?;
+        let result = match operator.token_type {
+            TokenType::Plus => left + right,
+            TokenType::Minus => left - right,
+            TokenType::Slash => left / right,
+            TokenType::Star => left * right,
+            TokenType::Percent => left % right,
+            TokenType::TildeSlash => left.int_div(right),
+            TokenType::StarStar => left.pow(right),
+            TokenType::Greater => {
+                let (x, y) = Self::check_number_operands(operator, &left, &right)?;
+                Ok(Value::Boolean(x > y))
+            }
+            TokenType::GreaterEqual => {
+                let (x, y) = Self::check_number_operands(operator, &left, &right)?;
+                Ok(Value::Boolean(x >= y))
+            }
+            TokenType::Less => {
+                let (x, y) = Self::check_number_operands(operator, &left, &right)?;
+                Ok(Value::Boolean(x < y))
+            }
+            TokenType::LessEqual => {
+                let (x, y) = Self::check_number_operands(operator, &left, &right)?;
+                Ok(Value::Boolean(x <= y))
+            }
+            TokenType::BangEqual => Ok(Value::Boolean(left != right)),
+            TokenType::EqualEqual => Ok(Value::Boolean(left == right)),
+            // the comma operator evaluates both sides and keeps the right
+            TokenType::Comma => Ok(right),
+            _ => unreachable!(),
+        };
+
+        result.map_err(|message| RuntimeError::new(operator.clone(), message))
+    }
+
+    fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<Value, RuntimeError> {
+        let right = self.evaluate(right)?;
+        let result = match operator.token_type {
+            TokenType::Minus => -right,
+            TokenType::Bang => Ok(Value::Boolean(!Self::is_truthy(&right))),
+            _ => unreachable!(),
+        };
+
+        result.map_err(|message| RuntimeError::new(operator.clone(), message))
+    }
+
+    fn visit_literal_expr(&mut self, value: &Literal) -> Result<Value, RuntimeError> {
+        Ok(value.into())
+    }
+
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<Value, RuntimeError> {
+        self.evaluate(expression)
+    }
+
+    fn visit_variable_expr(&mut self, id: ExprId, name: &Token) -> Result<Value, RuntimeError> {
+        self.look_up_variable(name, id)
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        id: ExprId,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.evaluate(value)?;
+        match self.locals.get(&id) {
+            Some(&distance) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(distance, name, value.clone())
             }
-            _ => panic!("Expr must be Expr::Binary variant"),
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
         }
+        Ok(value)
     }
 
-    fn evaluate(&self, expr: Expr) -> Literal {
-        todo!();
-        // expr.accept(&self)
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(left)?;
+
+        match operator.token_type {
+            TokenType::OR if Self::is_truthy(&left) => Ok(left),
+            TokenType::AND if !Self::is_truthy(&left) => Ok(left),
+            _ => self.evaluate(right),
+        }
     }
 
-    fn is_truthy(literal: Literal) -> Literal {
-        match literal {
-            Literal::Nil => Literal::Boolean(false),
-            Literal::Boolean(_) => literal,
-            _ => Literal::Boolean(true),
+    fn visit_call_expr(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(callee)?;
+
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let function: Rc<dyn LoxCallable> = match callee {
+            Value::Function(function) => function,
+            Value::Class(class) => class,
+            Value::Native(native) => native,
+            _ => {
+                return Err(RuntimeError::new(
+                    paren.clone(),
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+
+        if arguments.len() != function.arity() {
+            return Err(RuntimeError::new(
+                paren.clone(),
+                &format!(
+                    "Expected {} arguments but got {}.",
+                    function.arity(),
+                    arguments.len()
+                ),
+            ));
+        }
+
+        function.call(self, arguments)
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, name),
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn visit_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => {
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have fields.",
+            )),
+        }
+    }
+
+    fn visit_this_expr(&mut self, id: ExprId, keyword: &Token) -> Result<Value, RuntimeError> {
+        self.look_up_variable(keyword, id)
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        id: ExprId,
+        _keyword: &Token,
+        method: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let distance = self.locals[&id];
+        let superclass = match self.environment.borrow().get_at(distance, "super") {
+            Value::Class(class) => class,
+            _ => unreachable!(),
+        };
+
+        // "this" is always bound in the scope just inside the one holding "super"
+        let object = match self.environment.borrow().get_at(distance - 1, "this") {
+            Value::Instance(instance) => instance,
+            _ => unreachable!(),
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(function) => Ok(Value::Function(Rc::new(function.bind(object)))),
+            None => Err(RuntimeError::new(
+                method.clone(),
+                &format!("Undefined property '{}'.", method.lexeme),
+            )),
+        }
+    }
+
+    fn visit_interpolation_expr(&mut self, parts: &[Expr]) -> Result<Value, RuntimeError> {
+        let mut string = String::new();
+        for part in parts {
+            string += &self.evaluate(part)?.to_string();
+        }
+        Ok(Value::String(string.into()))
+    }
+
+    fn visit_ternary_expr(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Expr,
+        else_branch: &Expr,
+    ) -> Result<Value, RuntimeError> {
+        if Self::is_truthy(&self.evaluate(condition)?) {
+            self.evaluate(then_branch)
+        } else {
+            self.evaluate(else_branch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    /// Runs `source` as a script, stopping at the first runtime error
+    fn run(source: &str) -> Result<Interpreter, RuntimeError> {
+        let (tokens, errors) = Scanner::new(source.into()).scan_tokens();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|e| e[0].message.clone())
+            .unwrap();
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter).resolve(&statements);
+        for statement in &statements {
+            match interpreter.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(e)) => return Err(e),
+                Err(Unwind::Return(_)) => panic!("return at top level"),
+            }
+        }
+        Ok(interpreter)
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> String {
+        interpreter.globals.borrow().get_at(0, name).to_string()
+    }
+
+    #[test]
+    fn ternary_evaluates_only_the_chosen_branch() {
+        let interpreter = run("var calls = 0;
+            fun f() { calls = calls + 1; return 3; }
+            var a = true ? 1 : f();
+            var b = false ? f() : 2;
+            var c = nil ? f() : true ? 4 : f();
+            var d = true ? 5 : -\"not a number\";")
+        .unwrap();
+        for (name, expected) in [
+            ("calls", "0"),
+            ("a", "1"),
+            ("b", "2"),
+            ("c", "4"),
+            ("d", "5"),
+        ] {
+            assert_eq!(global(&interpreter, name), expected, "{}", name);
+        }
+
+        let interpreter = run("var calls = 0;
+            fun f() { calls = calls + 1; return 3; }
+            var a = false ? 1 : f();")
+        .unwrap();
+        assert_eq!(global(&interpreter, "calls"), "1");
+        assert_eq!(global(&interpreter, "a"), "3");
+    }
+
+    #[rustfmt::skip]
+    #[test]
+    fn runtime_type_errors() {
+        let cases = [
+            ("print -\"a\";", "Operand must be a number.", "-", 1, 7),
+            ("print 1 + \"x\";", "Operands must be two numbers or two strings.", "+", 1, 9),
+            ("print true < false;", "Operands must be numbers.", "<", 1, 12),
+            ("print 1 < \"a\";", "Operands must be numbers.", "<", 1, 9),
+            ("print nil >= 1;", "Operands must be numbers.", ">=", 1, 11),
+            ("print \"a\" - 1;", "Operands must be numbers.", "-", 1, 11),
+            ("print 2 * nil;", "Operands must be numbers.", "*", 1, 9),
+            ("var a = 1;\nprint a\n  / true;", "Operands must be numbers.", "/", 3, 3),
+        ];
+        for (source, message, lexeme, line, column) in cases {
+            let e = run(source).err().unwrap();
+            assert_eq!(
+                (e.message.as_str(), e.token.lexeme.as_str(), e.token.line, e.token.column),
+                (message, lexeme, line, column),
+                "{}",
+                source
+            );
         }
     }
 }
@@ -1,18 +1,21 @@
-#![feature(half_open_range_patterns)]
-#![feature(exclusive_range_pattern)]
-#![feature(precise_pointer_size_matching)]
 #![allow(dead_code)]
 #![warn(clippy::all)]
 
 use std::env;
-use std::process::exit;
 
+mod callable;
+mod class;
+mod environment;
 mod error;
 mod interpreter;
 mod parser;
+mod resolver;
 mod run;
 mod scanner;
 mod token;
+mod unicode;
+mod value;
+mod visitor;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -20,10 +23,6 @@ fn main() {
     match args.len() {
         0 => unreachable!(),
         1 => run::run_prompt(),
-        2 => run::run_file(&args[0]),
-        (3..) => {
-            println!("Usage: alox [script]");
-            exit(64);
-        }
+        _ => run::run_file(&args[1], args[2..].to_vec()),
     }
 }
@@ -1,21 +1,78 @@
 /*
-expression     → equality ;
+program        → declaration* EOF ;
+
+declaration    → classDecl
+               | funDecl
+               | varDecl
+               | statement ;
+classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )?
+                 "{" function* "}" ;
+funDecl        → "fun" function ;
+function       → IDENTIFIER "(" parameters? ")" block ;
+parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+
+statement      → exprStmt
+               | forStmt
+               | ifStmt
+               | printStmt
+               | returnStmt
+               | whileStmt
+               | block ;
+exprStmt       → expression ";" ;
+forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+                 expression? ";"
+                 expression? ")" statement ;
+ifStmt         → "if" "(" expression ")" statement
+               ( "else" statement )? ;
+printStmt      → "print" expression ";" ;
+returnStmt     → "return" expression? ";" ;
+whileStmt      → "while" "(" expression ")" statement ;
+block          → "{" declaration* "}" ;
+
+expression     → comma ;
+comma          → assignment ( "," assignment )* ;
+assignment     → ( call "." )? IDENTIFIER "=" assignment
+               | conditional ;
+conditional    → logic_or ( "?" expression ":" conditional )? ;
+logic_or       → logic_and ( "or" logic_and )* ;
+logic_and      → equality ( "and" equality )* ;
 equality       → comparison ( ( "!=" | "==" ) comparison )* ;
 comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
 term           → factor ( ( "-" | "+" ) factor )* ;
-factor         → unary ( ( "/" | "*" ) unary )* ;
+factor         → unary ( ( "/" | "*" | "%" | "~/" ) unary )* ;
 unary          → ( "!" | "-" ) unary
-               | primary ;
-primary        → NUMBER | STRING | "true" | "false" | "nil"
-               | "(" expression ")" ;
+               | exponent ;
+exponent       → call ( "**" unary )? ;
+call           → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+arguments      → assignment ( "," assignment )* ;
+primary        → "true" | "false" | "nil" | "this"
+               | NUMBER | STRING | IDENTIFIER | "(" expression ")"
+               | "super" "." IDENTIFIER | interpolation ;
+interpolation  → ( INTERPOLATION expression )+ STRING ;
 
 */
 use crate::{
-    error,
-    token::{Literal, Token, TokenType},
+    token::{Literal, Span, Token, TokenType},
+    visitor::{Visitor, VisitorMut, VisitorResult},
 };
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Debug)]
+/// Identifies a single expression node so the resolver can record where its
+/// variable lives. Unique across every parse in the process, since the REPL
+/// keeps one interpreter alive while parsing many lines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Expr {
     Binary {
         left: Box<Expr>,
@@ -26,25 +83,482 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
-    Literal(Literal),
-    Grouping(Box<Expr>),
+    Literal {
+        value: Literal,
+        span: Span,
+    },
+    Grouping {
+        expression: Box<Expr>,
+        span: Span,
+    },
+    Variable {
+        id: ExprId,
+        name: Token,
+    },
+    Assign {
+        id: ExprId,
+        name: Token,
+        value: Box<Expr>,
+    },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    },
+    This {
+        id: ExprId,
+        keyword: Token,
+    },
+    Super {
+        id: ExprId,
+        keyword: Token,
+        method: Token,
+    },
+    /// A string with embedded expressions; the string segments are literals
+    Interpolation {
+        parts: Vec<Expr>,
+        span: Span,
+    },
+    /// `condition ? then_branch : else_branch`
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
 }
 
 impl Expr {
-    pub fn accept(&self, other: Literal) {
-        todo!()
+    /// The source text this expression was parsed from
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                left.span().to(right.span())
+            }
+            Expr::Unary { operator, right } => operator.span.to(right.span()),
+            Expr::Literal { span, .. }
+            | Expr::Grouping { span, .. }
+            | Expr::Interpolation { span, .. } => *span,
+            Expr::Ternary {
+                condition,
+                else_branch,
+                ..
+            } => condition.span().to(else_branch.span()),
+            Expr::Variable { name, .. } => name.span,
+            Expr::Assign { name, value, .. } => name.span.to(value.span()),
+            Expr::Call { callee, paren, .. } => callee.span().to(paren.span),
+            Expr::Get { object, name } => object.span().to(name.span),
+            Expr::Set { object, value, .. } => object.span().to(value.span()),
+            Expr::This { keyword, .. } => keyword.span,
+            Expr::Super {
+                keyword, method, ..
+            } => keyword.span.to(method.span),
+        }
+    }
+
+    pub fn accept<R, V>(&self, visitor: &mut V) -> R
+    where
+        R: VisitorResult,
+        V: Visitor<R> + ?Sized,
+    {
+        match self {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => visitor.visit_binary_expr(left, operator, right),
+            Expr::Unary { operator, right } => visitor.visit_unary_expr(operator, right),
+            Expr::Literal { value, .. } => visitor.visit_literal_expr(value),
+            Expr::Grouping { expression, .. } => visitor.visit_grouping_expr(expression),
+            Expr::Variable { id, name } => visitor.visit_variable_expr(*id, name),
+            Expr::Assign { id, name, value } => visitor.visit_assign_expr(*id, name, value),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => visitor.visit_logical_expr(left, operator, right),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => visitor.visit_call_expr(callee, paren, arguments),
+            Expr::Get { object, name } => visitor.visit_get_expr(object, name),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => visitor.visit_set_expr(object, name, value),
+            Expr::This { id, keyword } => visitor.visit_this_expr(*id, keyword),
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => visitor.visit_super_expr(*id, keyword, method),
+            Expr::Interpolation { parts, .. } => visitor.visit_interpolation_expr(parts),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_ternary_expr(condition, then_branch, else_branch),
+        }
+    }
+
+    pub fn accept_mut<R, V>(&mut self, visitor: &mut V) -> R
+    where
+        R: VisitorResult,
+        V: VisitorMut<R> + ?Sized,
+    {
+        match self {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => visitor.visit_binary_expr_mut(left, operator, right),
+            Expr::Unary { operator, right } => visitor.visit_unary_expr_mut(operator, right),
+            Expr::Literal { value, .. } => visitor.visit_literal_expr_mut(value),
+            Expr::Grouping { expression, .. } => visitor.visit_grouping_expr_mut(expression),
+            Expr::Variable { id, name } => visitor.visit_variable_expr_mut(*id, name),
+            Expr::Assign { id, name, value } => visitor.visit_assign_expr_mut(*id, name, value),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => visitor.visit_logical_expr_mut(left, operator, right),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => visitor.visit_call_expr_mut(callee, paren, arguments),
+            Expr::Get { object, name } => visitor.visit_get_expr_mut(object, name),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => visitor.visit_set_expr_mut(object, name, value),
+            Expr::This { id, keyword } => visitor.visit_this_expr_mut(*id, keyword),
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => visitor.visit_super_expr_mut(*id, keyword, method),
+            Expr::Interpolation { parts, .. } => visitor.visit_interpolation_expr_mut(parts),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_ternary_expr_mut(condition, then_branch, else_branch),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    /// The `///` comments written above the declaration
+    pub doc: Option<Rc<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Block(Vec<Stmt>),
+    Class {
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+        doc: Option<Rc<String>>,
+    },
+    Expression(Expr),
+    Function(Rc<FunctionDecl>),
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    Print(Expr),
+    Return {
+        keyword: Token,
+        value: Option<Expr>,
+    },
+    Var {
+        name: Token,
+        initializer: Option<Expr>,
+        doc: Option<Rc<String>>,
+    },
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+    },
+}
+
+impl Stmt {
+    pub fn accept<R, V>(&self, visitor: &mut V) -> R
+    where
+        R: VisitorResult,
+        V: Visitor<R> + ?Sized,
+    {
+        match self {
+            Stmt::Block(statements) => visitor.visit_block_stmt(statements),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+            } => visitor.visit_class_stmt(
+                name,
+                superclass.as_ref(),
+                methods,
+                doc.as_deref().map(String::as_str),
+            ),
+            Stmt::Expression(expression) => visitor.visit_expression_stmt(expression),
+            Stmt::Function(declaration) => visitor.visit_function_stmt(declaration),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_if_stmt(condition, then_branch, else_branch.as_deref()),
+            Stmt::Print(expression) => visitor.visit_print_stmt(expression),
+            Stmt::Return { keyword, value } => visitor.visit_return_stmt(keyword, value.as_ref()),
+            Stmt::Var {
+                name,
+                initializer,
+                doc,
+            } => visitor.visit_var_stmt(
+                name,
+                initializer.as_ref(),
+                doc.as_deref().map(String::as_str),
+            ),
+            Stmt::While { condition, body } => visitor.visit_while_stmt(condition, body),
+        }
+    }
+
+    pub fn accept_mut<R, V>(&mut self, visitor: &mut V) -> R
+    where
+        R: VisitorResult,
+        V: VisitorMut<R> + ?Sized,
+    {
+        match self {
+            Stmt::Block(statements) => visitor.visit_block_stmt_mut(statements),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+            } => visitor.visit_class_stmt_mut(name, superclass.as_mut(), methods, doc),
+            Stmt::Expression(expression) => visitor.visit_expression_stmt_mut(expression),
+            Stmt::Function(declaration) => visitor.visit_function_stmt_mut(declaration),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_if_stmt_mut(condition, then_branch, else_branch.as_deref_mut()),
+            Stmt::Print(expression) => visitor.visit_print_stmt_mut(expression),
+            Stmt::Return { keyword, value } => {
+                visitor.visit_return_stmt_mut(keyword, value.as_mut())
+            }
+            Stmt::Var {
+                name,
+                initializer,
+                doc,
+            } => visitor.visit_var_stmt_mut(name, initializer.as_mut(), doc),
+            Stmt::While { condition, body } => visitor.visit_while_stmt_mut(condition, body),
+        }
+    }
+}
+
+const MAX_ARGUMENTS: usize = 255;
+
+// boxed so the happy path doesn't pay for moving a large error around
+type ParseResult<T> = Result<T, Box<ParseError>>;
+
+/// How tightly an infix operator binds, loosest first
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    None,
+    Comma,
+    Assignment,
+    Conditional,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Exponent,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter level, which is where a left-associative operator
+    /// parses its right operand
+    fn next(self) -> Self {
+        match self {
+            Precedence::None => Precedence::Comma,
+            Precedence::Comma => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Exponent,
+            Precedence::Exponent => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Parses an expression starting with the token just consumed
+type PrefixFn = fn(&mut Parser) -> ParseResult<Expr>;
+/// Parses the rest of an expression whose left operand has been parsed and
+/// whose operator was just consumed
+type InfixFn = fn(&mut Parser, Expr) -> ParseResult<Expr>;
+
+/// What a token does at the start of an expression and after an operand
+struct ParseRule {
+    token_type: TokenType,
+    prefix: Option<PrefixFn>,
+    infix: Option<InfixFn>,
+    /// How tightly `infix` binds
+    precedence: Precedence,
+}
+
+impl ParseRule {
+    const fn prefix(token_type: TokenType, prefix: PrefixFn) -> Self {
+        Self {
+            token_type,
+            prefix: Some(prefix),
+            infix: None,
+            precedence: Precedence::None,
+        }
+    }
+
+    const fn infix(token_type: TokenType, infix: InfixFn, precedence: Precedence) -> Self {
+        Self {
+            token_type,
+            prefix: None,
+            infix: Some(infix),
+            precedence,
+        }
     }
+
+    const fn both(
+        token_type: TokenType,
+        prefix: PrefixFn,
+        infix: InfixFn,
+        precedence: Precedence,
+    ) -> Self {
+        Self {
+            token_type,
+            prefix: Some(prefix),
+            infix: Some(infix),
+            precedence,
+        }
+    }
+
+    fn find(token_type: TokenType) -> Option<&'static ParseRule> {
+        RULES.iter().find(|rule| rule.token_type == token_type)
+    }
+}
+
+/// Whether `token_type` can continue an expression as an infix operator. The
+/// comma operator doesn't count, as commas also separate arguments.
+pub fn is_operator(token_type: TokenType) -> bool {
+    token_type != TokenType::Comma
+        && RULES
+            .iter()
+            .any(|rule| rule.token_type == token_type && rule.infix.is_some())
 }
 
-/// A recursive descent parser
+/// The expression grammar. Tokens without a rule can't appear in an
+/// expression, so adding an operator only takes a new row here.
+#[rustfmt::skip]
+const RULES: &[ParseRule] = &[
+    ParseRule::both(TokenType::LeftParen, Parser::grouping, Parser::call, Precedence::Call),
+    ParseRule::infix(TokenType::Comma, Parser::binary, Precedence::Comma),
+    ParseRule::infix(TokenType::Dot, Parser::dot, Precedence::Call),
+    ParseRule::both(TokenType::Minus, Parser::unary, Parser::binary, Precedence::Term),
+    ParseRule::infix(TokenType::Plus, Parser::binary, Precedence::Term),
+    ParseRule::infix(TokenType::Slash, Parser::binary, Precedence::Factor),
+    ParseRule::infix(TokenType::Star, Parser::binary, Precedence::Factor),
+    ParseRule::infix(TokenType::Percent, Parser::binary, Precedence::Factor),
+    ParseRule::infix(TokenType::TildeSlash, Parser::binary, Precedence::Factor),
+    ParseRule::infix(TokenType::StarStar, Parser::exponent, Precedence::Exponent),
+    ParseRule::infix(TokenType::Question, Parser::ternary, Precedence::Conditional),
+    ParseRule::prefix(TokenType::Bang, Parser::unary),
+    ParseRule::infix(TokenType::BangEqual, Parser::binary, Precedence::Equality),
+    ParseRule::infix(TokenType::Equal, Parser::assignment, Precedence::Assignment),
+    ParseRule::infix(TokenType::EqualEqual, Parser::binary, Precedence::Equality),
+    ParseRule::infix(TokenType::Greater, Parser::binary, Precedence::Comparison),
+    ParseRule::infix(TokenType::GreaterEqual, Parser::binary, Precedence::Comparison),
+    ParseRule::infix(TokenType::Less, Parser::binary, Precedence::Comparison),
+    ParseRule::infix(TokenType::LessEqual, Parser::binary, Precedence::Comparison),
+    ParseRule::prefix(TokenType::IDENTIFIER, Parser::variable),
+    ParseRule::prefix(TokenType::STRING, Parser::literal),
+    ParseRule::prefix(TokenType::INTERPOLATION, Parser::interpolation),
+    ParseRule::prefix(TokenType::NUMBER, Parser::literal),
+    ParseRule::infix(TokenType::AND, Parser::logical, Precedence::And),
+    ParseRule::prefix(TokenType::FALSE, Parser::literal),
+    ParseRule::prefix(TokenType::NIL, Parser::literal),
+    ParseRule::infix(TokenType::OR, Parser::logical, Precedence::Or),
+    ParseRule::prefix(TokenType::SUPER, Parser::super_method),
+    ParseRule::prefix(TokenType::THIS, Parser::this),
+    ParseRule::prefix(TokenType::TRUE, Parser::literal),
+    ParseRule::prefix(TokenType::ERROR, Parser::invalid),
+];
+
+/// A recursive descent parser, except for expressions, which are parsed by
+/// precedence climbing over `RULES`. On a syntax error it skips ahead to the next
+/// statement and carries on, so one parse reports every error in the source.
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
+    // every token type checked for at `current`, for error messages
+    expected: Vec<TokenType>,
+    // how many blocks enclose `current`
+    blocks: usize,
+    // whether the last expression statement may leave off its `;`
+    repl: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            expected: Vec::new(),
+            blocks: 0,
+            repl: false,
+        }
+    }
+
+    /// A parser for a line typed into the REPL, which can be a bare
+    /// expression with no `;`
+    pub fn for_repl(tokens: Vec<Token>) -> Self {
+        Self {
+            repl: true,
+            ..Self::new(tokens)
+        }
     }
 
     fn match_tokens(&mut self, types: &[TokenType]) -> bool {
@@ -57,13 +571,21 @@ impl Parser {
         false
     }
 
-    fn check(&self, token_type: TokenType) -> bool {
+    fn check(&mut self, token_type: TokenType) -> bool {
+        self.expect(token_type);
         if self.is_at_end() {
             return false;
         }
         self.peek().token_type == token_type
     }
 
+    /// Notes that `token_type` would have been accepted at `current`
+    fn expect(&mut self, token_type: TokenType) {
+        if !self.expected.contains(&token_type) {
+            self.expected.push(token_type);
+        }
+    }
+
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::EOF
     }
@@ -78,159 +600,966 @@ impl Parser {
 
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
-            self.current += 1
+            self.current += 1;
+            self.expected.clear();
         }
         self.previous()
     }
 
-    fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.equality()
+    /// Parses a declaration, or records the error and skips past the broken
+    /// statement if there isn't a valid one
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.try_declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(e) => {
+                if !self.after_scan_error(&e.token) {
+                    self.errors.push(*e);
+                }
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&mut self) -> ParseResult<Stmt> {
+        // doc comments are attached to the keyword starting the declaration
+        let doc = self.peek().doc.clone();
+        if self.match_tokens(&[TokenType::CLASS]) {
+            return self.class_declaration(doc);
+        }
+        if self.match_tokens(&[TokenType::FUN]) {
+            return Ok(Stmt::Function(Rc::new(self.function("function", doc)?)));
+        }
+        if self.match_tokens(&[TokenType::VAR]) {
+            return self.var_declaration(doc);
+        }
+        self.statement()
     }
 
-    fn equality(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.comparison()?;
+    fn class_declaration(&mut self, doc: Option<Rc<String>>) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expected class name!")?;
 
-        while self.match_tokens(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+        let superclass = if self.match_tokens(&[TokenType::Less]) {
+            self.consume(TokenType::IDENTIFIER, "Expected superclass name!")?;
+            Some(Expr::Variable {
+                id: ExprId::next(),
+                name: self.previous(),
+            })
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body!")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let doc = self.peek().doc.clone();
+            methods.push(Rc::new(self.function("method", doc)?));
         }
-        Ok(expr)
+
+        self.consume(TokenType::RightBrace, "Expected '}' after class body!")?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            doc,
+        })
     }
 
-    fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.term()?;
+    fn function(&mut self, kind: &str, doc: Option<Rc<String>>) -> ParseResult<FunctionDecl> {
+        let name = self.consume(TokenType::IDENTIFIER, &format!("Expected {} name!", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expected '(' after {} name!", kind),
+        )?;
 
-        while self.match_tokens(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let operator = self.previous();
-            let right = self.term()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let e = self.error(
+                        ParseErrorKind::TooManyParameters,
+                        self.peek().clone(),
+                        &format!("Can't have more than {} parameters!", MAX_ARGUMENTS),
+                    );
+                    self.errors.push(e);
+                }
+                params.push(self.consume(TokenType::IDENTIFIER, "Expected parameter name!")?);
+
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
         }
-        Ok(expr)
+        self.consume(TokenType::RightParen, "Expected ')' after parameters!")?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expected '{{' before {} body!", kind),
+        )?;
+        let body = self.block()?;
+
+        Ok(FunctionDecl {
+            name,
+            params,
+            body,
+            doc,
+        })
     }
-    fn term(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.factor()?;
 
-        while self.match_tokens(&[TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+    fn var_declaration(&mut self, doc: Option<Rc<String>>) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expected variable name!")?;
+
+        let initializer = if self.match_tokens(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expected ';' after variable declaration!",
+        )?;
+        Ok(Stmt::Var {
+            name,
+            initializer,
+            doc,
+        })
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        if self.match_tokens(&[TokenType::FOR]) {
+            return self.for_statement();
         }
-        Ok(expr)
+        if self.match_tokens(&[TokenType::IF]) {
+            return self.if_statement();
+        }
+        if self.match_tokens(&[TokenType::PRINT]) {
+            return self.print_statement();
+        }
+        if self.match_tokens(&[TokenType::RETURN]) {
+            return self.return_statement();
+        }
+        if self.match_tokens(&[TokenType::WHILE]) {
+            return self.while_statement();
+        }
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        self.expression_statement()
     }
-    fn factor(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.unary()?;
 
-        while self.match_tokens(&[TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut statements = Vec::new();
+
+        self.blocks += 1;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
-        Ok(expr)
+        self.blocks -= 1;
+
+        self.consume(TokenType::RightBrace, "Expected '}' after block!")?;
+        Ok(statements)
     }
-    fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_tokens(&[TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            return Ok(Expr::Unary {
-                operator,
-                right: Box::new(right),
-            });
+
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'for'!")?;
+
+        let initializer = if self.match_tokens(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_tokens(&[TokenType::VAR]) {
+            Some(self.var_declaration(None)?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(TokenType::Semicolon) {
+            self.expression()?
+        } else {
+            Expr::Literal {
+                value: Literal::Boolean(true),
+                span: self.peek().span,
+            }
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after loop condition!")?;
+
+        let increment = if !self.check(TokenType::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses!")?;
+
+        // desugar into a while loop
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
+        }
+
+        body = Stmt::While {
+            condition,
+            body: Box::new(body),
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'!")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after if condition!")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_tokens(&[TokenType::ELSE]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after value!")?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous();
+        let value = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, "Expected ';' after return value!")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'while'!")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after condition!")?;
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::While { condition, body })
+    }
+
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+        let expr = self.expression()?;
+        if !(self.repl && self.is_at_end()) {
+            self.consume(TokenType::Semicolon, "Expected ';' after expression!")?;
         }
-        self.primary()
+        Ok(Stmt::Expression(expr))
+    }
+
+    fn expression(&mut self) -> ParseResult<Expr> {
+        self.parse_precedence(Precedence::Comma)
     }
-    fn primary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_tokens(&[TokenType::FALSE]) {
-            return Ok(Expr::Literal(Literal::Boolean(false)));
+
+    /// An expression without a top-level comma, for where commas separate
+    /// things, like call arguments
+    fn assignment_expression(&mut self) -> ParseResult<Expr> {
+        self.parse_precedence(Precedence::Assignment)
+    }
+
+    /// Parses an expression made of operators binding at least as tightly as
+    /// `precedence`, starting with the prefix rule for the next token and
+    /// then folding in infix operators for as long as they bind tightly
+    /// enough
+    fn parse_precedence(&mut self, precedence: Precedence) -> ParseResult<Expr> {
+        let prefix = match ParseRule::find(self.peek().token_type).and_then(|rule| rule.prefix) {
+            Some(prefix) => prefix,
+            None => {
+                for rule in RULES.iter().filter(|rule| rule.prefix.is_some()) {
+                    self.expect(rule.token_type);
+                }
+                return Err(Box::new(self.unexpected(
+                    ParseErrorKind::ExpectedExpression,
+                    "Expected expression!",
+                )));
+            }
+        };
+        self.advance();
+        let mut expr = prefix(self)?;
+
+        loop {
+            match ParseRule::find(self.peek().token_type) {
+                Some(ParseRule {
+                    infix: Some(infix),
+                    precedence: infix_precedence,
+                    ..
+                }) if *infix_precedence >= precedence => {
+                    self.advance();
+                    expr = infix(self, expr)?;
+                }
+                _ => break,
+            }
         }
-        if self.match_tokens(&[TokenType::TRUE]) {
-            return Ok(Expr::Literal(Literal::Boolean(true)));
+
+        // anything that could have continued the expression
+        for rule in RULES {
+            if rule.infix.is_some() && rule.precedence >= precedence {
+                self.expect(rule.token_type);
+            }
+        }
+        Ok(expr)
+    }
+
+    fn grouping(&mut self) -> ParseResult<Expr> {
+        let left = self.previous();
+        let expr = self.expression()?;
+        let right = self.consume(TokenType::RightParen, "Expected ')' after expression!")?;
+        Ok(Expr::Grouping {
+            expression: Box::new(expr),
+            span: left.span.to(right.span),
+        })
+    }
+
+    fn literal(&mut self) -> ParseResult<Expr> {
+        let token = self.previous();
+        let value = match token.token_type {
+            TokenType::FALSE => Literal::Boolean(false),
+            TokenType::TRUE => Literal::Boolean(true),
+            TokenType::NIL => Literal::Nil,
+            _ => token.literal.unwrap(),
+        };
+        Ok(Expr::Literal {
+            value,
+            span: token.span,
+        })
+    }
+
+    /// Stands in for text the scanner couldn't make sense of, so that after
+    /// the scanner's error the parser carries on as if it were a value
+    fn invalid(&mut self) -> ParseResult<Expr> {
+        Ok(Expr::Literal {
+            value: Literal::Nil,
+            span: self.previous().span,
+        })
+    }
+
+    fn variable(&mut self) -> ParseResult<Expr> {
+        Ok(Expr::Variable {
+            id: ExprId::next(),
+            name: self.previous(),
+        })
+    }
+
+    fn this(&mut self) -> ParseResult<Expr> {
+        Ok(Expr::This {
+            id: ExprId::next(),
+            keyword: self.previous(),
+        })
+    }
+
+    fn super_method(&mut self) -> ParseResult<Expr> {
+        let keyword = self.previous();
+        self.consume(TokenType::Dot, "Expected '.' after 'super'!")?;
+        let method = self.consume(TokenType::IDENTIFIER, "Expected superclass method name!")?;
+        Ok(Expr::Super {
+            id: ExprId::next(),
+            keyword,
+            method,
+        })
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        let operator = self.previous();
+        let right = self.parse_precedence(Precedence::Unary)?;
+        Ok(Expr::Unary {
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    /// Parses the right operand of a left-associative binary operator
+    fn right_operand(&mut self, operator: &Token) -> ParseResult<Expr> {
+        let precedence = ParseRule::find(operator.token_type).unwrap().precedence;
+        self.parse_precedence(precedence.next())
+    }
+
+    fn binary(&mut self, left: Expr) -> ParseResult<Expr> {
+        let operator = self.previous();
+        let right = self.right_operand(&operator)?;
+        Ok(Expr::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    /// `**` is right-associative and its right operand may be negated, so
+    /// `2 ** -1` and `2 ** 3 ** 2` work as they do in maths. It binds tighter
+    /// than a unary minus on its left, making `-2 ** 2` equal to `-4`.
+    fn exponent(&mut self, left: Expr) -> ParseResult<Expr> {
+        let operator = self.previous();
+        let right = self.parse_precedence(Precedence::Unary)?;
+        Ok(Expr::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    fn logical(&mut self, left: Expr) -> ParseResult<Expr> {
+        let operator = self.previous();
+        let right = self.right_operand(&operator)?;
+        Ok(Expr::Logical {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    /// Assignment is right-associative, so `a = b = c` assigns `c` to both
+    fn assignment(&mut self, target: Expr) -> ParseResult<Expr> {
+        let equals = self.previous();
+        let value = self.parse_precedence(Precedence::Assignment)?;
+
+        match target {
+            Expr::Variable { name, .. } => Ok(Expr::Assign {
+                id: ExprId::next(),
+                name,
+                value: Box::new(value),
+            }),
+            Expr::Get { object, name } => Ok(Expr::Set {
+                object,
+                name,
+                value: Box::new(value),
+            }),
+            _ => {
+                // the parser isn't confused, so report it without unwinding
+                let e = self.error(
+                    ParseErrorKind::InvalidAssignmentTarget,
+                    equals,
+                    "Invalid assignment target!",
+                );
+                self.errors.push(e);
+                Ok(target)
+            }
         }
+    }
+
+    /// The conditional operator is right-associative, so `a ? b : c ? d : e`
+    /// is `a ? b : (c ? d : e)`
+    fn ternary(&mut self, condition: Expr) -> ParseResult<Expr> {
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            "Expected ':' after then branch of conditional expression!",
+        )?;
+        let else_branch = self.parse_precedence(Precedence::Conditional)?;
+        Ok(Expr::Ternary {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
+    }
 
-        if self.match_tokens(&[TokenType::NUMBER, TokenType::STRING]) {
-            return Ok(Expr::Literal(self.previous().literal.unwrap()));
+    fn call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let e = self.error(
+                        ParseErrorKind::TooManyArguments,
+                        self.peek().clone(),
+                        &format!("Can't have more than {} arguments!", MAX_ARGUMENTS),
+                    );
+                    self.errors.push(e);
+                }
+                arguments.push(self.assignment_expression()?);
+
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
         }
 
-        if self.match_tokens(&[TokenType::LeftParen]) {
-            let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expected ')' after expression!")?;
-            return Ok(Expr::Grouping(Box::new(expr)));
+        let paren = self.consume(TokenType::RightParen, "Expected ')' after arguments!")?;
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    fn dot(&mut self, object: Expr) -> ParseResult<Expr> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expected property name after '.'!")?;
+        Ok(Expr::Get {
+            object: Box::new(object),
+            name,
+        })
+    }
+
+    /// Called after the first `INTERPOLATION` segment has been matched
+    fn interpolation(&mut self) -> ParseResult<Expr> {
+        let start = self.previous().span;
+        let mut parts = Vec::new();
+        loop {
+            let segment = self.previous();
+            parts.push(Expr::Literal {
+                value: segment.literal.unwrap(),
+                span: segment.span,
+            });
+            parts.push(self.expression()?);
+
+            if !self.match_tokens(&[TokenType::INTERPOLATION]) {
+                break;
+            }
         }
 
-        Err(ParseError {
-            tokens: self.tokens.clone(),
-            current: self.current,
-            message: format!("{}\n{}", self.peek(), "Expected expression!"),
+        // the scanner turns the closing `}` into the rest of the string, so
+        // that's what to report as missing
+        let end = self
+            .consume(
+                TokenType::STRING,
+                "Expected '}' after interpolated expression!",
+            )
+            .map_err(|mut e| {
+                e.kind = ParseErrorKind::MissingClosingBrace;
+                e.expected
+                    .retain(|&t| t != TokenType::STRING && t != TokenType::INTERPOLATION);
+                e.expected.push(TokenType::RightBrace);
+                e
+            })?;
+        parts.push(Expr::Literal {
+            value: end.literal.unwrap(),
+            span: end.span,
+        });
+
+        Ok(Expr::Interpolation {
+            parts,
+            span: start.to(end.span),
         })
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ParseError> {
+    fn consume(&mut self, token_type: TokenType, message: &str) -> ParseResult<Token> {
         if self.check(token_type) {
             return Ok(self.advance());
         }
-        panic!("{}\n{}", self.peek(), message);
+        let kind = match token_type {
+            TokenType::RightParen => ParseErrorKind::MissingClosingParen,
+            TokenType::RightBrace => ParseErrorKind::MissingClosingBrace,
+            TokenType::Semicolon => ParseErrorKind::MissingSemicolon,
+            _ => ParseErrorKind::UnexpectedToken,
+        };
+        Err(Box::new(self.unexpected(kind, message)))
     }
 
-    fn error(self, token: Token, message: &str) -> ParseError {
-        error::error(token, message);
+    /// An error about `token` that doesn't depend on what comes next
+    fn error(&self, kind: ParseErrorKind, token: Token, message: &str) -> ParseError {
         ParseError {
-            tokens: self.tokens,
-            current: self.current,
+            kind,
+            token,
+            expected: Vec::new(),
             message: message.into(),
         }
     }
+
+    /// An error about the next token not being one that could go here
+    fn unexpected(&self, kind: ParseErrorKind, message: &str) -> ParseError {
+        ParseError {
+            expected: self.expected.clone(),
+            ..self.error(kind, self.peek().clone(), message)
+        }
+    }
+
+    /// Discards tokens until the start of what is probably the next statement
     fn synchronize(&mut self) {
+        if self.at_block_end() {
+            return;
+        }
         self.advance();
 
         while !self.is_at_end() {
-            if self.previous().token_type == TokenType::Semicolon {
+            if self.previous().token_type == TokenType::Semicolon || self.at_block_end() {
                 return;
             }
 
-            // discard tokens until there's a statement boundary
             match self.peek().token_type {
-                TokenType::CLASS => {}
-                TokenType::FUN => {}
-                TokenType::VAR => {}
-                TokenType::FOR => {}
-                TokenType::IF => {}
-                TokenType::WHILE => {}
-                TokenType::PRINT => {}
-                TokenType::RETURN => return,
-                _ => unreachable!(),
-            };
+                TokenType::CLASS
+                | TokenType::FUN
+                | TokenType::VAR
+                | TokenType::FOR
+                | TokenType::IF
+                | TokenType::WHILE
+                | TokenType::PRINT
+                | TokenType::RETURN => return,
+                _ => {}
+            }
             self.advance();
         }
     }
 
-    pub fn parse(&mut self) -> Option<Expr> {
-        self.expression().ok()
+    /// Whether an error at `token` would only repeat one the scanner already
+    /// reported: `token` is the bad text itself, or the end of a source whose
+    /// rest was swallowed by an unterminated string or comment
+    fn after_scan_error(&self, token: &Token) -> bool {
+        match token.token_type {
+            TokenType::ERROR => true,
+            TokenType::EOF => matches!(
+                self.tokens.iter().rev().nth(1),
+                Some(previous) if previous.token_type == TokenType::ERROR
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the next token closes the block being parsed, which
+    /// `synchronize` leaves for `block` to match. Outside any block a `}` is
+    /// just a stray token to skip.
+    fn at_block_end(&self) -> bool {
+        self.blocks > 0 && self.peek().token_type == TokenType::RightBrace
+    }
+
+    /// Parses the whole program, returning every syntax error if there were any
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 }
 
-struct ParseError {
-    tokens: Vec<Token>,
-    current: usize,
-    message: String,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedToken,
+    ExpectedExpression,
+    MissingClosingParen,
+    MissingClosingBrace,
+    MissingSemicolon,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    TooManyParameters,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// The token the error was found at
+    pub token: Token,
+    /// The token types that would have been accepted instead of `token`.
+    /// Empty for errors that aren't about an unexpected token.
+    pub expected: Vec<TokenType>,
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    /// Prints an expression as an s-expression, leaving out the `ExprId`s,
+    /// which differ on every parse
+    fn sexpr(expr: &Expr) -> String {
+        match expr {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+            } => format!("({} {} {})", operator.lexeme, sexpr(left), sexpr(right)),
+            Expr::Unary { operator, right } => format!("({} {})", operator.lexeme, sexpr(right)),
+            Expr::Literal { value, span } => format!("{:?}@{}..{}", value, span.start, span.end),
+            Expr::Grouping { expression, span } => {
+                format!("(group {})@{}..{}", sexpr(expression), span.start, span.end)
+            }
+            Expr::Variable { name, .. } => name.lexeme.clone(),
+            Expr::Assign { name, value, .. } => format!("(= {} {})", name.lexeme, sexpr(value)),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let arguments: Vec<String> = arguments.iter().map(sexpr).collect();
+                format!("(call {} [{}])", sexpr(callee), arguments.join(" "))
+            }
+            Expr::Get { object, name } => format!("(. {} {})", sexpr(object), name.lexeme),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => format!("(.= {} {} {})", sexpr(object), name.lexeme, sexpr(value)),
+            Expr::This { .. } => "this".into(),
+            Expr::Super { method, .. } => format!("(super {})", method.lexeme),
+            Expr::Interpolation { parts, span } => {
+                let parts: Vec<String> = parts.iter().map(sexpr).collect();
+                format!("(interp {})@{}..{}", parts.join(" "), span.start, span.end)
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => format!(
+                "(? {} {} {})",
+                sexpr(condition),
+                sexpr(then_branch),
+                sexpr(else_branch)
+            ),
+        }
+    }
+
+    fn parse(source: &str) -> String {
+        let (tokens, errors) = Scanner::new(source.into()).scan_tokens();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut parser = Parser::new(tokens);
+        let expr = parser.expression().map_err(|e| e.message).unwrap();
+        assert!(parser.is_at_end(), "{} left tokens behind", source);
+        let errors: Vec<String> = parser.errors.iter().map(|e| e.message.clone()).collect();
+        format!("{} {:?}", sexpr(&expr), errors)
+    }
+
+    // The expected trees were produced by the recursive descent parser this
+    // one replaced, one method per precedence level
+    #[test]
+    fn same_trees_as_recursive_descent() {
+        let cases = [
+            (
+                "1 + 2 * 3",
+                "(+ Number(1.0)@0..1 (* Number(2.0)@4..5 Number(3.0)@8..9)) []",
+            ),
+            (
+                "(1 + 2) * 3",
+                "(* (group (+ Number(1.0)@1..2 Number(2.0)@5..6))@0..7 Number(3.0)@10..11) []",
+            ),
+            (
+                "1 - 2 - 3",
+                "(- (- Number(1.0)@0..1 Number(2.0)@4..5) Number(3.0)@8..9) []",
+            ),
+            (
+                "8 / 4 / 2 * 3",
+                "(* (/ (/ Number(8.0)@0..1 Number(4.0)@4..5) Number(2.0)@8..9) Number(3.0)@12..13) []",
+            ),
+            (
+                "-1 - -2",
+                "(- (- Number(1.0)@1..2) (- Number(2.0)@6..7)) []",
+            ),
+            (
+                "!!true",
+                "(! (! Boolean(true)@2..6)) []",
+            ),
+            (
+                "a = b = c",
+                "(= a (= b c)) []",
+            ),
+            (
+                "a.b.c = d",
+                "(.= (. a b) c d) []",
+            ),
+            (
+                "x.y = z = 1 + 2",
+                "(.= x y (= z (+ Number(1.0)@10..11 Number(2.0)@14..15))) []",
+            ),
+            (
+                "a or b and c or d",
+                "(or (or a (and b c)) d) []",
+            ),
+            (
+                "a and b or c and d",
+                "(or (and a b) (and c d)) []",
+            ),
+            (
+                "a == b != c",
+                "(!= (== a b) c) []",
+            ),
+            (
+                "1 < 2 == 3 >= 4",
+                "(== (< Number(1.0)@0..1 Number(2.0)@4..5) (>= Number(3.0)@9..10 Number(4.0)@14..15)) []",
+            ),
+            (
+                "1 + 2 < 3 - 4 * 5",
+                "(< (+ Number(1.0)@0..1 Number(2.0)@4..5) (- Number(3.0)@8..9 (* Number(4.0)@12..13 Number(5.0)@16..17))) []",
+            ),
+            (
+                "-a.b(c)(d, e).f",
+                "(- (. (call (call (. a b) [c]) [d e]) f)) []",
+            ),
+            (
+                "f()",
+                "(call f []) []",
+            ),
+            (
+                "super.m(this)",
+                "(call (super m) [this]) []",
+            ),
+            (
+                "nil",
+                "Nil@0..3 []",
+            ),
+            (
+                "false == !true",
+                "(== Boolean(false)@0..5 (! Boolean(true)@10..14)) []",
+            ),
+            (
+                r#""s ${a + 1} t""#,
+                r#"(interp String("s ")@0..5 (+ a Number(1.0)@9..10) String(" t")@10..14)@0..14 []"#,
+            ),
+            (
+                "a = 1 + 2 * -3 < 4 or !b",
+                "(= a (or (< (+ Number(1.0)@4..5 (* Number(2.0)@8..9 (- Number(3.0)@13..14))) Number(4.0)@17..18) (! b))) []",
+            ),
+            (
+                "1 + a = 2",
+                r#"(+ Number(1.0)@0..1 a) ["Invalid assignment target!"]"#,
+            ),
+            (
+                "-a = 3",
+                r#"(- a) ["Invalid assignment target!"]"#,
+            ),
+            (
+                "(a) = 4",
+                r#"(group a)@0..3 ["Invalid assignment target!"]"#,
+            ),
+            (
+                "a.b(c).d = e or f",
+                "(.= (call (. a b) [c]) d (or e f)) []",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(parse(source), *expected, "parsing {}", source);
+        }
+    }
+
+    #[test]
+    fn ternary_and_comma() {
+        let cases = [
+            ("a ? b : c ? d : e", "(? a b (? c d e)) []"),
+            ("a or b ? c : d", "(? (or a b) c d) []"),
+            ("a == b ? c : d", "(? (== a b) c d) []"),
+            ("x = a ? b : c", "(= x (? a b c)) []"),
+            ("a ? x = b : c", "(? a (= x b) c) []"),
+            ("a, b = c, d", "(, (, a (= b c)) d) []"),
+            ("f(a, b ? c : d)", "(call f [a (? b c d)]) []"),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(parse(source), *expected, "parsing {}", source);
+        }
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        let cases = [
+            ("-a ** b", "(- (** a b)) []"),
+            ("a ** b ** c", "(** a (** b c)) []"),
+            ("a ** -b", "(** a (- b)) []"),
+            ("a * b ** c", "(* a (** b c)) []"),
+            ("a.b ** c(d)", "(** (. a b) (call c [d])) []"),
+            ("a + b % c ~/ d", "(+ a (~/ (% b c) d)) []"),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(parse(source), *expected, "parsing {}", source);
+        }
+    }
+    /// The kind of each error in `source`, with the non-operator tokens
+    /// that would have been accepted instead
+    fn parse_errors(source: &str) -> Vec<(ParseErrorKind, Vec<TokenType>)> {
+        let (tokens, _) = Scanner::new(source.into()).scan_tokens();
+        let errors = Parser::new(tokens).parse().err().unwrap_or_default();
+        errors
+            .into_iter()
+            .map(|e| {
+                let expected = e.expected.into_iter().filter(|&t| !is_operator(t));
+                (e.kind, expected.collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expected_tokens() {
+        use ParseErrorKind::*;
+        use TokenType::*;
+        #[rustfmt::skip]
+        let cases = [
+            ("fun f(a b) {}", vec![(MissingClosingParen, vec![Comma, RightParen])]),
+            ("print f(1 2);", vec![(MissingClosingParen, vec![Comma, RightParen])]),
+            ("class A { 1 }", vec![(UnexpectedToken, vec![RightBrace, IDENTIFIER])]),
+            ("print \"${1 2}\";", vec![(MissingClosingBrace, vec![Comma, RightBrace])]),
+            ("var 1; print 1 2;", vec![
+                (UnexpectedToken, vec![IDENTIFIER]),
+                (MissingSemicolon, vec![Comma, Semicolon]),
+            ]),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_errors(source), expected, "parsing {}", source);
+        }
+    }
+
+    #[test]
+    fn recovery_stops_at_block_end() {
+        use ParseErrorKind::*;
+        let cases = [
+            ("fun f() { print 1 }\nprint 2;", MissingSemicolon),
+            ("class A { m() { return 1 } }\nprint 2;", MissingSemicolon),
+            ("{ var x = 1 }\n{ print x; }", MissingSemicolon),
+            ("while (true) { print ) }\nprint 2;", ExpectedExpression),
+            // a stray `}` outside any block is skipped
+            ("print 1; } print 2;", ExpectedExpression),
+        ];
+        for (source, kind) in cases {
+            let kinds: Vec<_> = parse_errors(source)
+                .into_iter()
+                .map(|(kind, _)| kind)
+                .collect();
+            assert_eq!(kinds, [kind], "parsing {}", source);
+        }
+    }
+
+    #[test]
+    fn repl_allows_a_bare_expression() {
+        let parse_repl = |source: &str| {
+            let (tokens, _) = Scanner::new(source.into()).scan_tokens();
+            Parser::for_repl(tokens)
+                .parse()
+                .map(|statements| statements.len())
+        };
+        assert_eq!(parse_repl("1 + 2").ok(), Some(1));
+        assert_eq!(parse_repl("var a = 1; a").ok(), Some(2));
+        // only the last expression may leave off its `;`
+        assert!(parse_repl("1 + 2 print 3;").is_err());
+        assert!(parse_repl("var a = 1").is_err());
+        assert_eq!(parse_errors("1 + 2").len(), 1);
+    }
+
+    // the scanner reports these, so the parser shouldn't add to them
+    #[test]
+    fn no_errors_after_scan_errors() {
+        let cases = [
+            "var x = @;",
+            "var @ = 1;",
+            "print 1 + € * 2;",
+            "{ var y = 2 @ }",
+            "print \"abc",
+            "var x = 1 /* a",
+        ];
+        for source in cases {
+            assert_eq!(parse_errors(source), [], "parsing {}", source);
+        }
+        assert_eq!(parse_errors("var x = @ 1;").len(), 1);
+    }
 }
@@ -0,0 +1,336 @@
+use crate::{
+    error,
+    interpreter::Interpreter,
+    parser::{Expr, ExprId, FunctionDecl, Stmt},
+    token::Token,
+    visitor::{self, Visitor},
+};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Walks the AST once before it is run, telling the interpreter how many
+/// scopes out each local variable lives, and reporting static errors
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    // each scope maps a name to whether its initializer has finished
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn resolve_function(&mut self, function: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if scope.contains_key(&name.lexeme) {
+                error::error(
+                    name.clone(),
+                    "Already a variable with this name in this scope.",
+                );
+            }
+            scope.insert(name.lexeme.clone(), false);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(id, depth);
+                return;
+            }
+        }
+    }
+}
+
+impl Visitor<()> for Resolver<'_> {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) {
+        self.begin_scope();
+        self.resolve(statements);
+        self.end_scope();
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+        _doc: Option<&str>,
+    ) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable {
+                name: superclass_name,
+                ..
+            } = superclass
+            {
+                if superclass_name.lexeme == name.lexeme {
+                    error::error(
+                        superclass_name.clone(),
+                        "A class can't inherit from itself.",
+                    );
+                }
+            }
+
+            self.current_class = ClassType::Subclass;
+            superclass.accept(self);
+
+            self.begin_scope();
+            self.scopes.last_mut().unwrap().insert("super".into(), true);
+        }
+
+        self.begin_scope();
+        self.scopes.last_mut().unwrap().insert("this".into(), true);
+
+        for method in methods {
+            let declaration = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, declaration);
+        }
+
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn visit_function_stmt(&mut self, declaration: &Rc<FunctionDecl>) {
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+        self.resolve_function(declaration, FunctionType::Function);
+    }
+
+    fn visit_return_stmt(&mut self, keyword: &Token, value: Option<&Expr>) {
+        if self.current_function == FunctionType::None {
+            error::error(keyword.clone(), "Can't return from top-level code.");
+        }
+
+        if value.is_some() && self.current_function == FunctionType::Initializer {
+            error::error(keyword.clone(), "Can't return a value from an initializer.");
+        }
+
+        visitor::walk_return_stmt(self, keyword, value)
+    }
+
+    fn visit_var_stmt(&mut self, name: &Token, initializer: Option<&Expr>, _doc: Option<&str>) {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            initializer.accept(self);
+        }
+        self.define(name);
+    }
+
+    fn visit_variable_expr(&mut self, id: ExprId, name: &Token) {
+        if let Some(false) = self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) {
+            error::error(
+                name.clone(),
+                "Can't read local variable in its own initializer.",
+            );
+        }
+        self.resolve_local(id, name);
+    }
+
+    fn visit_assign_expr(&mut self, id: ExprId, name: &Token, value: &Expr) {
+        value.accept(self);
+        self.resolve_local(id, name);
+    }
+
+    fn visit_this_expr(&mut self, id: ExprId, keyword: &Token) {
+        if self.current_class == ClassType::None {
+            error::error(keyword.clone(), "Can't use 'this' outside of a class.");
+            return;
+        }
+        self.resolve_local(id, keyword);
+    }
+
+    fn visit_super_expr(&mut self, id: ExprId, keyword: &Token, _method: &Token) {
+        match self.current_class {
+            ClassType::None => {
+                error::error(keyword.clone(), "Can't use 'super' outside of a class.")
+            }
+            ClassType::Class => error::error(
+                keyword.clone(),
+                "Can't use 'super' in a class with no superclass.",
+            ),
+            ClassType::Subclass => self.resolve_local(id, keyword),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn resolve(source: &str) -> (Interpreter, Vec<Stmt>, Vec<String>) {
+        let (tokens, errors) = Scanner::new(source.into()).scan_tokens();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|e| e[0].message.clone())
+            .unwrap();
+        let mut interpreter = Interpreter::new();
+        error::take_reported();
+        Resolver::new(&mut interpreter).resolve(&statements);
+        (interpreter, statements, error::take_reported())
+    }
+
+    #[rustfmt::skip]
+    #[test]
+    fn static_errors() {
+        let cases = [
+            ("{ var a = a; }", "[line 1:11] Error at 'a': Can't read local variable in its own initializer."),
+            ("{ var a = 1; var a = 2; }", "[line 1:18] Error at 'a': Already a variable with this name in this scope."),
+            ("fun f(a, a) {}", "[line 1:10] Error at 'a': Already a variable with this name in this scope."),
+            ("return 1;", "[line 1:1] Error at 'return': Can't return from top-level code."),
+            ("print this;", "[line 1:7] Error at 'this': Can't use 'this' outside of a class."),
+            ("fun f() { return this; }", "[line 1:18] Error at 'this': Can't use 'this' outside of a class."),
+            ("class A { m() { return super.m(); } }", "[line 1:24] Error at 'super': Can't use 'super' in a class with no superclass."),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(resolve(source).2, [expected], "{}", source);
+        }
+
+        // globals may be redeclared, and a global may shadow itself
+        for source in ["var a = 1; var a = 2;", "var a = a;", "fun f() { return 1; }"] {
+            assert_eq!(resolve(source).2, Vec::<String>::new(), "{}", source);
+        }
+    }
+
+    /// Lists every variable use in the order it appears, with the number of
+    /// scopes out the resolver found it, or `None` for globals
+    struct Depths<'a>(&'a Interpreter, Vec<(String, Option<usize>)>);
+
+    impl Depths<'_> {
+        fn record(&mut self, id: ExprId, name: &Token) {
+            self.1
+                .push((name.lexeme.clone(), self.0.resolved_depth(id)));
+        }
+    }
+
+    impl Visitor<()> for Depths<'_> {
+        fn visit_variable_expr(&mut self, id: ExprId, name: &Token) {
+            self.record(id, name);
+        }
+
+        fn visit_assign_expr(&mut self, id: ExprId, name: &Token, value: &Expr) {
+            self.record(id, name);
+            visitor::walk_assign_expr(self, id, name, value)
+        }
+
+        fn visit_this_expr(&mut self, id: ExprId, keyword: &Token) {
+            self.record(id, keyword);
+        }
+
+        fn visit_super_expr(&mut self, id: ExprId, keyword: &Token, _method: &Token) {
+            self.record(id, keyword);
+        }
+    }
+
+    #[test]
+    fn scope_distances() {
+        let (interpreter, statements, errors) = resolve(
+            "var g = 1;
+            {
+                var a = 1;
+                {
+                    var b = a;
+                    b = g;
+                    fun f(c) { return a + b + c; }
+                }
+            }
+            class A { m() { return this; } }
+            class B < A { m() { return super.m(); } }",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut depths = Depths(&interpreter, Vec::new());
+        for statement in &statements {
+            statement.accept(&mut depths);
+        }
+        let expected = [
+            ("a", Some(1)),
+            ("b", Some(0)),
+            ("g", None),
+            ("a", Some(2)),
+            ("b", Some(1)),
+            ("c", Some(0)),
+            ("this", Some(1)),
+            ("A", None),
+            ("super", Some(2)),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(name, depth)| (name.to_string(), depth))
+            .collect();
+        assert_eq!(depths.1, expected);
+    }
+}
@@ -1,33 +1,82 @@
-use crate::{parser::Parser, scanner::Scanner};
+use crate::{
+    error,
+    interpreter::Interpreter,
+    parser::{Parser, Stmt},
+    resolver::Resolver,
+    scanner::Scanner,
+};
 use std::fs;
 use std::io;
 use std::io::Write;
+use std::process::exit;
 
-pub fn run(source: String) {
+/// Runs `source` as a whole program. In the REPL a line that is just an
+/// expression prints its value, with or without the `;`.
+pub fn run(source: String, interpreter: &mut Interpreter, repl: bool) {
     let scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
+    let (tokens, scan_errors) = scanner.scan_tokens();
+    for e in &scan_errors {
+        error::scan_error(e);
+    }
+
+    let mut parser = if repl {
+        Parser::for_repl(tokens)
+    } else {
+        Parser::new(tokens)
+    };
+    let statements = parser.parse();
+
+    let statements = match statements {
+        Ok(statements) if !error::had_error() => statements,
+        Ok(_) => return,
+        Err(errors) => {
+            for e in &errors {
+                error::parse_error(e);
+            }
+            return;
+        }
+    };
+
+    Resolver::new(interpreter).resolve(&statements);
 
-    let mut parser = Parser::new(tokens);
-    let expression = parser.parse();
+    // don't run code with static errors in it
+    if error::had_error() {
+        return;
+    }
 
-    if let Some(e) = expression {
-        println!("{:?}", e)
+    match statements.as_slice() {
+        [Stmt::Expression(expr)] if repl => interpreter.interpret_expression(expr),
+        _ => interpreter.interpret(statements),
     }
 }
 
-pub fn run_file(path: &str) {
+/// Runs the script at `path`, passing `args` through to its `args()`
+pub fn run_file(path: &str, args: Vec<String>) {
     let filecontents = fs::read_to_string(path).expect("Error reading file");
-    run(filecontents);
+    let mut interpreter = Interpreter::with_args(args);
+    run(filecontents, &mut interpreter, false);
+
+    if error::had_error() {
+        exit(65);
+    }
+    if error::had_runtime_error() {
+        exit(70);
+    }
 }
 
 pub fn run_prompt() {
+    let mut interpreter = Interpreter::new();
     loop {
         let mut input = String::new();
         print!(">");
         io::stdout().flush().unwrap();
-        io::stdin()
+        let bytes = io::stdin()
             .read_line(&mut input)
             .expect("Failed to read line");
-        run(input);
+        if bytes == 0 {
+            break;
+        }
+        run(input, &mut interpreter, true);
+        error::reset_error();
     }
 }
@@ -1,70 +1,140 @@
-use crate::{
-    error,
-    token::{Literal, Token, TokenType},
-};
+use crate::token::{Literal, Span, Token, TokenType};
+use crate::unicode;
+use std::rc::Rc;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScanErrorKind {
+    UnexpectedChar,
+    UnterminatedString,
+    UnterminatedComment,
+    BadEscape,
+    BadNumber,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+// what scanning from `start` produced: nothing for whitespace and comments
+type Scanned = Option<Result<Token, ScanError>>;
+
+/// Turns source text into tokens in a single pass. `start` and `current` are
+/// byte offsets into `source` and only ever land on char boundaries, so
+/// slicing the source with them is always safe.
+///
+/// Tokens are produced lazily by iterating over the scanner, ending with a
+/// single `EOF` token.
 pub struct Scanner {
     source: String,
-    tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    // chars consumed so far on the current line
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    reached_eof: bool,
+    // one entry per `${` we're inside, counting the `{`s opened within it
+    interpolations: Vec<usize>,
+    // `///` comments waiting to be attached to the next token
+    doc: Option<String>,
+    // a token to hand out after the error found in its text
+    pending: Option<Token>,
 }
 
 impl Default for Scanner {
     fn default() -> Scanner {
-        Scanner {
-            source: String::new(),
-            tokens: Vec::new(),
-            start: 0,
-            current: 0,
-            line: 1,
-        }
+        Scanner::new(String::new())
     }
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
+        // skip a `#!` line so scripts can be run directly, but leave its
+        // newline to be scanned so line numbers stay right
+        let current = if source.starts_with("#!") {
+            source.find('\n').unwrap_or(source.len())
+        } else {
+            0
+        };
+
         Self {
             source,
-            tokens: Vec::new(),
             start: 0,
-            current: 0,
+            current,
             line: 1,
+            column: 0,
+            start_line: 1,
+            start_column: 1,
+            reached_eof: false,
+            interpolations: Vec::new(),
+            doc: None,
+            pending: None,
         }
     }
-    pub fn scan_tokens(mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
-        }
-
-        self.tokens.push(Token {
-            token_type: TokenType::EOF,
-            lexeme: String::new(),
-            literal: None,
-            line: self.line,
-        });
 
-        self.tokens
+    /// Scans the whole source, collecting every lexical error rather than
+    /// stopping at the first one
+    pub fn scan_tokens(self) -> (Vec<Token>, Vec<ScanError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => errors.push(e),
+            }
+        }
+        (tokens, errors)
     }
 
     fn is_at_end(&self) -> bool {
-        return self.current >= self.source.chars().count();
+        self.current >= self.source.len()
     }
 
-    fn scan_token(&mut self) {
+    fn scan_token(&mut self) -> Scanned {
         match self.advance() {
             '(' => self.add_token(TokenType::LeftParen, None),
             ')' => self.add_token(TokenType::RightParen, None),
-            '{' => self.add_token(TokenType::LeftBrace, None),
-            '}' => self.add_token(TokenType::RightBrace, None),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace, None)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // closes the `${`, so what follows is more of the string
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string()
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace, None)
+                }
+                None => self.add_token(TokenType::RightBrace, None),
+            },
             ',' => self.add_token(TokenType::Comma, None),
             '.' => self.add_token(TokenType::Dot, None),
             '-' => self.add_token(TokenType::Minus, None),
             '+' => self.add_token(TokenType::Plus, None),
             ';' => self.add_token(TokenType::Semicolon, None),
-            '*' => self.add_token(TokenType::Star, None),
+            '*' => {
+                if self.match_char('*') {
+                    self.add_token(TokenType::StarStar, None)
+                } else {
+                    self.add_token(TokenType::Star, None)
+                }
+            }
+            '%' => self.add_token(TokenType::Percent, None),
+            // integer division, since `//` starts a comment
+            '~' if self.match_char('/') => self.add_token(TokenType::TildeSlash, None),
+            '?' => self.add_token(TokenType::Question, None),
+            ':' => self.add_token(TokenType::Colon, None),
             '!' => {
                 if self.match_char('=') {
                     self.add_token(TokenType::BangEqual, None)
@@ -95,122 +165,396 @@ impl Scanner {
             }
             '/' => {
                 if self.match_char('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
+                    self.line_comment();
+                    None
+                } else if self.match_char('*') {
+                    self.block_comment()
                 } else {
                     self.add_token(TokenType::Slash, None)
                 }
             }
             ' ' | '\r' | '\t' => {
                 //ignore whitespace
+                None
             }
             '"' => self.string(),
-            '\n' => self.line += 1,
+            'r' if self.peek() == '"' => self.raw_string(),
+            '\n' => {
+                self.new_line();
+                None
+            }
             c if Self::is_digit(c) => self.number(),
             c if Self::is_alpha(c) => self.identifier(),
-            _ => error::error(self.tokens[self.current].clone(), "Unexpected character!"),
-        };
+            _ => self.error_token(ScanErrorKind::UnexpectedChar, "Unexpected character!"),
+        }
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source.chars().nth(self.current - 1).unwrap()
+        let c = self.peek();
+        self.current += c.len_utf8();
+        self.column += 1;
+        c
+    }
+
+    fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) -> Scanned {
+        Some(Ok(self.make_token(token_type, literal)))
     }
 
-    fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
+    fn make_token(&mut self, token_type: TokenType, literal: Option<Literal>) -> Token {
         let text = &self.source[self.start..self.current];
-        self.tokens.push(Token {
+        Token {
             token_type,
             lexeme: text.into(),
             literal,
-            line: self.line,
-        })
+            line: self.start_line,
+            column: self.start_column,
+            span: Span::new(self.start, self.current),
+            doc: self.doc.take().map(Rc::new),
+        }
     }
 
-    fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
+    fn make_error(&self, kind: ScanErrorKind, message: &str) -> ScanError {
+        ScanError {
+            kind,
+            message: message.into(),
+            line: self.start_line,
+            column: self.start_column,
+            span: Span::new(self.start, self.current),
+        }
+    }
+
+    /// Reports `error`, then hands out the literal it was found in as the
+    /// next token, so the parser doesn't pile its own errors on top
+    fn literal_error(
+        &mut self,
+        error: ScanError,
+        token_type: TokenType,
+        literal: Literal,
+    ) -> Scanned {
+        self.pending = Some(self.make_token(token_type, Some(literal)));
+        Some(Err(error))
+    }
+
+    /// Reports an error about the text scanned since `start`, then hands that
+    /// text out as an `ERROR` token, which the parser takes for an expression
+    fn error_token(&mut self, kind: ScanErrorKind, message: &str) -> Scanned {
+        let error = self.make_error(kind, message);
+        self.pending = Some(self.make_token(TokenType::ERROR, None));
+        Some(Err(error))
+    }
+
+    fn number_error(&mut self, message: &str) -> Scanned {
+        let error = self.make_error(ScanErrorKind::BadNumber, message);
+        self.literal_error(error, TokenType::NUMBER, Literal::Number(0.0))
+    }
+
+    /// Call after consuming `//`. A `///` comment (but not `////`) is
+    /// documentation and is kept for the next token.
+    fn line_comment(&mut self) {
+        let is_doc = self.peek() == '/' && self.peek_next() != '/';
+        let text_start = self.current + 1;
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+
+        if is_doc {
+            let text = self.source[text_start..self.current].trim_end();
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            match &mut self.doc {
+                Some(doc) => {
+                    doc.push('\n');
+                    doc.push_str(text);
+                }
+                None => self.doc = Some(text.into()),
+            }
+        }
+    }
+
+    /// Call after consuming `/*`. Block comments nest, so each `/*` inside
+    /// needs its own `*/`.
+    fn block_comment(&mut self) -> Scanned {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                return self.error_token(
+                    ScanErrorKind::UnterminatedComment,
+                    "Unterminated block comment! Try adding a */",
+                );
+            }
+
+            match self.advance() {
+                '/' if self.match_char('*') => depth += 1,
+                '*' if self.match_char('/') => depth -= 1,
+                '\n' => self.new_line(),
+                _ => {}
+            }
         }
+        None
+    }
 
-        if self.source.chars().nth(self.current).unwrap() != expected {
+    /// Call after consuming a '\n'
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 0;
+    }
+
+    fn match_char(&mut self, expected: char) -> bool {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
 
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn peek(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    /// Scans a string literal, translating escape sequences. A bad escape
+    /// doesn't stop the scan, so the rest of the string is still consumed.
+    ///
+    /// Called after the opening `"`, or after the `}` ending an interpolated
+    /// expression. A `${` ends the segment as an `INTERPOLATION` token and
+    /// the expression's tokens follow it.
+    fn string(&mut self) -> Scanned {
+        let mut value = String::new();
+        let mut bad_escape = None;
+        while self.peek() != '"' && !self.is_at_end() {
+            match self.advance() {
+                '\\' => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(e) => {
+                        bad_escape.get_or_insert(e);
+                    }
+                },
+                '\n' => {
+                    self.new_line();
+                    value.push('\n');
+                }
+                '$' if self.match_char('{') => {
+                    self.interpolations.push(0);
+                    return match bad_escape {
+                        Some(e) => {
+                            self.literal_error(e, TokenType::INTERPOLATION, Literal::String(value))
+                        }
+                        None => {
+                            self.add_token(TokenType::INTERPOLATION, Some(Literal::String(value)))
+                        }
+                    };
+                }
+                c => value.push(c),
+            }
+        }
+
         if self.is_at_end() {
-            return '\0';
+            return self.unterminated_string();
         }
 
-        self.source.chars().nth(self.current).unwrap()
+        self.advance(); // the other "
+
+        match bad_escape {
+            Some(e) => self.literal_error(e, TokenType::STRING, Literal::String(value)),
+            None => self.add_token(TokenType::STRING, Some(Literal::String(value))),
+        }
     }
 
-    fn string(&mut self) {
+    /// Scans a raw string literal `r"..."`, in which backslashes are just
+    /// backslashes. The opening `r` has already been consumed.
+    fn raw_string(&mut self) -> Scanned {
+        self.advance(); // the opening "
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.advance() == '\n' {
+                self.new_line();
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            error::error(
-                self.tokens[self.current].clone(),
-                "Unterminated string! Try adding a \"",
-            );
-            return;
+            return self.unterminated_string();
         }
 
-        self.advance(); // the other "
+        self.advance(); // the closing "
 
-        // trim the quotes
-        let value = self.source[self.start + 1..self.current - 1].into();
+        // trim the r and the quotes
+        let value = self.source[self.start + 2..self.current - 1].into();
 
         self.add_token(TokenType::STRING, Some(Literal::String(value)))
     }
 
+    fn unterminated_string(&mut self) -> Scanned {
+        self.error_token(
+            ScanErrorKind::UnterminatedString,
+            "Unterminated string! Try adding a \"",
+        )
+    }
+
+    /// Call after consuming a '\\' inside a string. Returns the escaped char.
+    fn escape(&mut self) -> Result<char, ScanError> {
+        let start = self.current - 1;
+        let line = self.line;
+        let column = self.column;
+        let bad_escape = |scanner: &Self, message: &str| ScanError {
+            kind: ScanErrorKind::BadEscape,
+            message: message.into(),
+            line,
+            column,
+            span: Span::new(start, scanner.current),
+        };
+
+        if self.is_at_end() {
+            return Err(bad_escape(self, "Expected an escape sequence after '\\'!"));
+        }
+
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            '0' => Ok('\0'),
+            'u' => {
+                if !self.match_char('{') {
+                    return Err(bad_escape(self, "Expected '{' after '\\u'!"));
+                }
+
+                let digits_start = self.current;
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let digits_end = self.current;
+
+                if !self.match_char('}') {
+                    return Err(bad_escape(self, "Expected '}' to close unicode escape!"));
+                }
+
+                let digits = &self.source[digits_start..digits_end];
+                if digits.is_empty() || digits.len() > 6 {
+                    return Err(bad_escape(
+                        self,
+                        "Unicode escape must have between 1 and 6 hex digits!",
+                    ));
+                }
+
+                u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| bad_escape(self, "Unicode escape is not a valid char!"))
+            }
+            c => {
+                if c == '\n' {
+                    self.new_line();
+                }
+                Err(bad_escape(
+                    self,
+                    &format!("Unknown escape sequence '\\{}'!", c.escape_default()),
+                ))
+            }
+        }
+    }
+
     fn is_digit(c: char) -> bool {
-        ('0'..='9').contains(&c)
+        c.is_ascii_digit()
     }
 
-    fn number(&mut self) {
-        while Self::is_digit(self.peek()) {
-            self.advance();
+    /// Scans a decimal literal with optional fraction and exponent, or a
+    /// `0x`, `0b` or `0o` integer. Any literal may use `_` between digits.
+    fn number(&mut self) -> Scanned {
+        if &self.source[self.start..self.current] == "0" {
+            match self.peek() {
+                'x' | 'X' => return self.radix_number(16, "hex"),
+                'b' | 'B' => return self.radix_number(2, "binary"),
+                'o' | 'O' => return self.radix_number(8, "octal"),
+                _ => {}
+            }
         }
 
+        let mut separators_ok = self.digits(self.start, 10);
+
         // look for fraction part
         if self.peek() == '.' && Self::is_digit(self.peek_next()) {
             self.advance();
+            separators_ok &= self.digits(self.current, 10);
+        }
 
-            while Self::is_digit(self.peek()) {
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+            if !Self::is_digit(self.peek()) {
+                return self.number_error("Expected digits in exponent!");
+            }
+            separators_ok &= self.digits(self.current, 10);
+        }
+
+        if !separators_ok {
+            return self.number_error("Digit separators must be between digits!");
         }
 
-        let number = self.source[self.start..self.current].parse().unwrap();
-        self.add_token(TokenType::NUMBER, Some(Literal::Number(number)));
+        let text = self.source[self.start..self.current].replace('_', "");
+        match text.parse() {
+            Ok(number) => self.add_token(TokenType::NUMBER, Some(Literal::Number(number))),
+            Err(_) => self.number_error("Invalid number literal!"),
+        }
     }
 
-    fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
+    /// Call after consuming the leading `0`, with the radix letter next
+    fn radix_number(&mut self, radix: u32, name: &str) -> Scanned {
+        self.advance();
+        let digits_start = self.current;
+        // take anything that looks like part of the literal, then check it
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+
+        if digits.is_empty() {
+            let prefix = &self.source[self.start..digits_start];
+            return self.number_error(&format!("Expected {} digits after '{}'!", name, prefix));
+        }
+        if let Some(c) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            return self.number_error(&format!("Invalid digit '{}' in {} literal!", c, name));
+        }
+        if !Self::separators_ok(digits) {
+            return self.number_error("Digit separators must be between digits!");
         }
 
-        self.source.chars().nth(self.current + 1).unwrap()
+        match u64::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(number) => self.add_token(TokenType::NUMBER, Some(Literal::Number(number as f64))),
+            Err(_) => self.number_error("Number literal is too large!"),
+        }
     }
 
+    /// Consumes the rest of a run of digits and `_` separators beginning at
+    /// `start`, and returns whether the separators were all between digits
+    fn digits(&mut self, start: usize, radix: u32) -> bool {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
+        }
+        Self::separators_ok(&self.source[start..self.current])
+    }
+
+    fn separators_ok(digits: &str) -> bool {
+        !digits.starts_with('_') && !digits.ends_with('_') && !digits.contains("__")
+    }
+
+    fn peek_next(&self) -> char {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
+    }
+
+    /// Whether `c` can start an identifier
     fn is_alpha(c: char) -> bool {
-        ('a'..='z').contains(&c) || ('A'..='Z').contains(&c) || c == '_'
+        c == '_' || unicode::is_xid_start(c)
     }
 
+    /// Whether `c` can appear after the first character of an identifier
     fn is_alphanumeric(c: char) -> bool {
-        Self::is_alpha(c) || Self::is_digit(c)
+        unicode::is_xid_continue(c)
     }
-    fn identifier(&mut self) {
+    fn identifier(&mut self) -> Scanned {
         while Self::is_alphanumeric(self.peek()) {
             self.advance();
         }
@@ -238,3 +582,288 @@ impl Scanner {
         }
     }
 }
+
+impl Iterator for Scanner {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.take() {
+            return Some(Ok(token));
+        }
+
+        while !self.is_at_end() {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column + 1;
+            if let Some(result) = self.scan_token() {
+                return Some(result);
+            }
+        }
+
+        if self.reached_eof {
+            return None;
+        }
+        self.reached_eof = true;
+
+        Some(Ok(Token {
+            token_type: TokenType::EOF,
+            lexeme: String::new(),
+            literal: None,
+            line: self.line,
+            column: self.column + 1,
+            span: Span::new(self.current, self.current),
+            doc: self.doc.take().map(Rc::new),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scans `source`, which must be free of errors, leaving off the `EOF`
+    fn tokens(source: &str) -> Vec<(TokenType, Option<Literal>)> {
+        let (tokens, errors) = Scanner::new(source.into()).scan_tokens();
+        assert!(errors.is_empty(), "scanning {}: {:?}", source, errors);
+        tokens
+            .into_iter()
+            .filter(|token| token.token_type != TokenType::EOF)
+            .map(|token| (token.token_type, token.literal))
+            .collect()
+    }
+
+    /// Scans `source`, which must have exactly one error, and checks that
+    /// error's kind, message and span
+    fn assert_error(source: &str, kind: ScanErrorKind, message: &str, span: (usize, usize)) {
+        let (_, errors) = Scanner::new(source.into()).scan_tokens();
+        assert_eq!(errors.len(), 1, "scanning {}: {:?}", source, errors);
+        let e = &errors[0];
+        assert_eq!(
+            (e.kind, e.message.as_str(), (e.span.start, e.span.end)),
+            (kind, message, span),
+            "scanning {}",
+            source
+        );
+    }
+
+    fn string(value: &str) -> (TokenType, Option<Literal>) {
+        (TokenType::STRING, Some(Literal::String(value.into())))
+    }
+
+    #[test]
+    fn strings() {
+        let cases = [
+            (r#""abc""#, "abc"),
+            (r#""""#, ""),
+            (r#""a\nb\tc\rd""#, "a\nb\tc\rd"),
+            (r#""\\ \" \$ \0""#, "\\ \" $ \0"),
+            (r#""\u{41}\u{e9}\u{1F600}""#, "A\u{e9}\u{1F600}"),
+            ("\"two\nlines\"", "two\nlines"),
+            (r#"r"a\nb\""#, "a\\nb\\"),
+            ("r\"raw\nlines\"", "raw\nlines"),
+        ];
+        for (source, value) in cases {
+            assert_eq!(tokens(source), [string(value)], "scanning {}", source);
+        }
+    }
+
+    #[test]
+    fn string_errors() {
+        use ScanErrorKind::*;
+        #[rustfmt::skip]
+        let cases = [
+            (r#""a\qb""#, BadEscape, "Unknown escape sequence '\\q'!", (2, 4)),
+            (r#""\u41""#, BadEscape, "Expected '{' after '\\u'!", (1, 3)),
+            (r#""\u{41""#, BadEscape, "Expected '}' to close unicode escape!", (1, 6)),
+            (r#""\u{}""#, BadEscape, "Unicode escape must have between 1 and 6 hex digits!", (1, 5)),
+            (r#""\u{1234567}""#, BadEscape, "Unicode escape must have between 1 and 6 hex digits!", (1, 12)),
+            (r#""\u{D800}""#, BadEscape, "Unicode escape is not a valid char!", (1, 9)),
+            ("\"abc", UnterminatedString, "Unterminated string! Try adding a \"", (0, 4)),
+            ("r\"abc", UnterminatedString, "Unterminated string! Try adding a \"", (0, 5)),
+        ];
+        for (source, kind, message, span) in cases {
+            assert_error(source, kind, message, span);
+        }
+    }
+
+    fn interpolation(value: &str) -> (TokenType, Option<Literal>) {
+        (
+            TokenType::INTERPOLATION,
+            Some(Literal::String(value.into())),
+        )
+    }
+
+    #[test]
+    fn interpolations() {
+        use TokenType::*;
+        let x = || (IDENTIFIER, None);
+        #[rustfmt::skip]
+        let cases = [
+            (r#""a${x}b""#, vec![interpolation("a"), x(), string("b")]),
+            (r#""${x}${x}""#, vec![interpolation(""), x(), interpolation(""), x(), string("")]),
+            (r#""${"${x}"}""#, vec![interpolation(""), interpolation(""), x(), string(""), string("")]),
+            // braces inside the expression don't end it
+            (r#""${ {} }.""#, vec![interpolation(""), (LeftBrace, None), (RightBrace, None), string(".")]),
+            (r#""\${x}""#, vec![string("${x}")]),
+            (r#""$x {x}""#, vec![string("$x {x}")]),
+            (r#"r"${x}""#, vec![string("${x}")]),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(tokens(source), expected, "scanning {}", source);
+        }
+    }
+
+    #[test]
+    fn interpolation_errors() {
+        use ScanErrorKind::*;
+        #[rustfmt::skip]
+        let cases = [
+            (r#""a${x}b"#, UnterminatedString, "Unterminated string! Try adding a \"", (5, 7)),
+            (r#""a${x}\q""#, BadEscape, "Unknown escape sequence '\\q'!", (6, 8)),
+            (r#""\q${x}""#, BadEscape, "Unknown escape sequence '\\q'!", (1, 3)),
+        ];
+        for (source, kind, message, span) in cases {
+            assert_error(source, kind, message, span);
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        let cases = [
+            ("123", 123.0),
+            ("1.5", 1.5),
+            ("1_000_000", 1e6),
+            ("1e3", 1e3),
+            ("1.5E-3", 1.5e-3),
+            ("2e+2", 200.0),
+            ("0xFF", 255.0),
+            ("0Xdead_BEEF", 3735928559.0),
+            ("0b1010", 10.0),
+            ("0o17", 15.0),
+            // not octal
+            ("017", 17.0),
+        ];
+        for (source, value) in cases {
+            let expected = [(TokenType::NUMBER, Some(Literal::Number(value)))];
+            assert_eq!(tokens(source), expected, "scanning {}", source);
+        }
+
+        // a trailing dot is a method call, not a fraction
+        assert_eq!(
+            tokens("1.e"),
+            [
+                (TokenType::NUMBER, Some(Literal::Number(1.0))),
+                (TokenType::Dot, None),
+                (TokenType::IDENTIFIER, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn number_errors() {
+        use ScanErrorKind::*;
+        #[rustfmt::skip]
+        let cases = [
+            ("0x", BadNumber, "Expected hex digits after '0x'!", (0, 2)),
+            ("0b", BadNumber, "Expected binary digits after '0b'!", (0, 2)),
+            ("0b102", BadNumber, "Invalid digit '2' in binary literal!", (0, 5)),
+            ("0o8", BadNumber, "Invalid digit '8' in octal literal!", (0, 3)),
+            ("0xfg", BadNumber, "Invalid digit 'g' in hex literal!", (0, 4)),
+            ("0x1_0000_0000_0000_0000", BadNumber, "Number literal is too large!", (0, 23)),
+            ("0x_1", BadNumber, "Digit separators must be between digits!", (0, 4)),
+            ("1_", BadNumber, "Digit separators must be between digits!", (0, 2)),
+            ("1__0", BadNumber, "Digit separators must be between digits!", (0, 4)),
+            ("1.5_e3", BadNumber, "Digit separators must be between digits!", (0, 6)),
+            ("1e", BadNumber, "Expected digits in exponent!", (0, 2)),
+            ("1e+", BadNumber, "Expected digits in exponent!", (0, 3)),
+        ];
+        for (source, kind, message, span) in cases {
+            assert_error(source, kind, message, span);
+        }
+
+        // the literal is still handed to the parser after its error
+        let (tokens, _) = Scanner::new("0x;".into()).scan_tokens();
+        let types: Vec<_> = tokens.iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            types,
+            [TokenType::NUMBER, TokenType::Semicolon, TokenType::EOF]
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        // `e\u{301}` is an `e` followed by a combining acute accent
+        for source in ["café", "π", "変数", "_x1", "naïve_2", "e\u{301}", "classy"] {
+            let (tokens, errors) = Scanner::new(source.into()).scan_tokens();
+            assert!(errors.is_empty(), "scanning {}: {:?}", source, errors);
+            let lexemes: Vec<_> = tokens
+                .iter()
+                .map(|t| (t.token_type, t.lexeme.as_str()))
+                .collect();
+            assert_eq!(
+                lexemes,
+                [(TokenType::IDENTIFIER, source), (TokenType::EOF, "")]
+            );
+        }
+    }
+
+    #[test]
+    fn unexpected_characters() {
+        use ScanErrorKind::*;
+        // spans are in bytes
+        let cases = [
+            ("@", UnexpectedChar, "Unexpected character!", (0, 1)),
+            ("a € b", UnexpectedChar, "Unexpected character!", (2, 5)),
+            ("😀", UnexpectedChar, "Unexpected character!", (0, 4)),
+            ("\u{301}", UnexpectedChar, "Unexpected character!", (0, 2)),
+        ];
+        for (source, kind, message, span) in cases {
+            assert_error(source, kind, message, span);
+        }
+    }
+
+    /// Text that can't be scanned is handed to the parser as an `ERROR` token
+    #[test]
+    fn error_tokens() {
+        use TokenType::*;
+        let cases = [
+            ("a € b", vec![IDENTIFIER, ERROR, IDENTIFIER, EOF]),
+            ("print \"abc", vec![PRINT, ERROR, EOF]),
+            ("1 /* a", vec![NUMBER, ERROR, EOF]),
+        ];
+        for (source, expected) in cases {
+            let (tokens, _) = Scanner::new(source.into()).scan_tokens();
+            let types: Vec<_> = tokens.iter().map(|token| token.token_type).collect();
+            assert_eq!(types, expected, "scanning {}", source);
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let cases = [
+            ("1 // 2", 1),
+            ("1 /* 2 */ 3", 2),
+            ("1 /* /* 2 */ 3 */ 4", 2),
+            ("/* a\n/*\nb */ */", 0),
+        ];
+        for (source, count) in cases {
+            assert_eq!(tokens(source).len(), count, "scanning {}", source);
+        }
+
+        let (tokens, _) = Scanner::new("/// A\n///  B\n//// C\nvar".into()).scan_tokens();
+        assert_eq!(tokens[0].doc.as_deref().map(String::as_str), Some("A\n B"));
+    }
+
+    #[test]
+    fn comment_errors() {
+        use ScanErrorKind::*;
+        #[rustfmt::skip]
+        let cases = [
+            ("/* a", UnterminatedComment, "Unterminated block comment! Try adding a */", (0, 4)),
+            ("/* /* */", UnterminatedComment, "Unterminated block comment! Try adding a */", (0, 8)),
+        ];
+        for (source, kind, message, span) in cases {
+            assert_error(source, kind, message, span);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TokenType {
     // Single-character tokens.
@@ -14,6 +16,9 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Question,
+    Colon,
 
     // One or two character tokens.
     Bang,
@@ -24,10 +29,14 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    TildeSlash,
 
     // Literals.
     IDENTIFIER,
     STRING,
+    /// A string segment that ends at `${`, followed by the embedded expression
+    INTERPOLATION,
     NUMBER,
 
     // Keywords.
@@ -47,15 +56,96 @@ pub enum TokenType {
     TRUE,
     VAR,
     WHILE,
+    /// Text the scanner couldn't make sense of, which it has already reported
+    ERROR,
     EOF,
 }
 
+impl TokenType {
+    /// How the token type is written in error messages
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::LeftParen => "`(`",
+            Self::RightParen => "`)`",
+            Self::LeftBrace => "`{`",
+            Self::RightBrace => "`}`",
+            Self::Comma => "`,`",
+            Self::Dot => "`.`",
+            Self::Minus => "`-`",
+            Self::Plus => "`+`",
+            Self::Semicolon => "`;`",
+            Self::Slash => "`/`",
+            Self::Star => "`*`",
+            Self::Percent => "`%`",
+            Self::Question => "`?`",
+            Self::Colon => "`:`",
+            Self::Bang => "`!`",
+            Self::BangEqual => "`!=`",
+            Self::Equal => "`=`",
+            Self::EqualEqual => "`==`",
+            Self::Greater => "`>`",
+            Self::GreaterEqual => "`>=`",
+            Self::Less => "`<`",
+            Self::LessEqual => "`<=`",
+            Self::StarStar => "`**`",
+            Self::TildeSlash => "`~/`",
+            Self::IDENTIFIER => "an identifier",
+            Self::STRING => "a string",
+            Self::INTERPOLATION => "an interpolated string",
+            Self::NUMBER => "a number",
+            Self::AND => "`and`",
+            Self::CLASS => "`class`",
+            Self::ELSE => "`else`",
+            Self::FALSE => "`false`",
+            Self::FUN => "`fun`",
+            Self::FOR => "`for`",
+            Self::IF => "`if`",
+            Self::NIL => "`nil`",
+            Self::OR => "`or`",
+            Self::PRINT => "`print`",
+            Self::RETURN => "`return`",
+            Self::SUPER => "`super`",
+            Self::THIS => "`this`",
+            Self::TRUE => "`true`",
+            Self::VAR => "`var`",
+            Self::WHILE => "`while`",
+            Self::ERROR => "an invalid token",
+            Self::EOF => "end of file",
+        }
+    }
+}
+
+/// Byte offsets of a piece of source text, `start` inclusive and `end` exclusive
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: Option<Literal>,
     pub line: usize,
+    /// 1-based column of the token's first character
+    pub column: usize,
+    pub span: Span,
+    /// Text of the `///` comments directly before this token, if any. An
+    /// `Rc<String>` rather than `Rc<str>` keeps it one pointer wide, since
+    /// tokens are copied into every error.
+    pub doc: Option<Rc<String>>,
 }
 
 impl Display for Token {
@@ -71,80 +161,13 @@ pub enum Literal {
     String(String),
 }
 
-impl std::ops::Neg for Literal {
-    type Output = Self;
-
-    fn neg(self) -> Self {
-        match self {
-            Self::Number(n) => Self::Number(-n),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl std::ops::Not for Literal {
-    type Output = Self;
-
-    fn not(self) -> Self {
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Boolean(n) => Self::Boolean(!n),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl std::ops::Add for Literal {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        match (self, other) {
-            (Self::Number(x), Self::Number(y)) => Self::Number(x + y),
-            (Self::String(x), Self::String(y)) => Self::String(x + &y),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl std::ops::Sub for Literal {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self {
-        match (self, other) {
-            (Self::Number(x), Self::Number(y)) => Self::Number(x - y),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl std::ops::Mul for Literal {
-    type Output = Self;
-
-    fn mul(self, other: Self) -> Self {
-        match (self, other) {
-            (Self::Number(x), Self::Number(y)) => Self::Number(x * y),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl std::ops::Div for Literal {
-    type Output = Self;
-
-    fn div(self, other: Self) -> Self {
-        match (self, other) {
-            (Self::Number(x), Self::Number(y)) => Self::Number(x / y),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl PartialOrd for Literal {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Self::Number(x), Self::Number(y)) => x.partial_cmp(y),
-            (Self::String(x), Self::String(y)) => Some(x.cmp(y)),
-            (Self::Boolean(x), Self::Boolean(y)) => Some(x.cmp(y)),
-            _ => None,
+            Self::Nil => write!(f, "nil"),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write!(f, "{}", s),
         }
     }
 }
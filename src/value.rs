@@ -0,0 +1,247 @@
+use crate::{
+    callable::{LoxFunction, NativeFunction},
+    class::{LoxClass, LoxInstance},
+    token::Literal,
+};
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+/// A value produced at runtime. Everything that isn't a number or bool sits
+/// behind an `Rc`, so cloning a value never copies its contents.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Native(Rc<NativeFunction>),
+    List(Rc<[Value]>),
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Nil => Self::Nil,
+            Literal::Boolean(b) => Self::Boolean(*b),
+            Literal::Number(n) => Self::Number(*n),
+            Literal::String(s) => Self::String(s.as_str().into()),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::Boolean(x), Self::Boolean(y)) => x == y,
+            (Self::Number(x), Self::Number(y)) => x == y,
+            (Self::String(x), Self::String(y)) => x == y,
+            (Self::Function(x), Self::Function(y)) => Rc::ptr_eq(x, y),
+            (Self::Class(x), Self::Class(y)) => Rc::ptr_eq(x, y),
+            (Self::Instance(x), Self::Instance(y)) => Rc::ptr_eq(x, y),
+            (Self::Native(x), Self::Native(y)) => Rc::ptr_eq(x, y),
+            (Self::List(x), Self::List(y)) => Rc::ptr_eq(x, y),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nil => write!(f, "nil"),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Number(n) => fmt_number(*n, f),
+            Self::String(s) => write!(f, "{}", s),
+            Self::Function(function) => write!(f, "{}", function),
+            Self::Class(class) => write!(f, "{}", class),
+            Self::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Self::Native(native) => write!(f, "{}", native),
+            Self::List(list) => {
+                write!(f, "[")?;
+                for (i, value) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Prints whole numbers without a fraction and switches to exponent notation
+/// for very large and very small magnitudes, where JavaScript does, so `1e300`
+/// doesn't print as 301 digits
+fn fmt_number(n: f64, f: &mut Formatter<'_>) -> fmt::Result {
+    if n.is_nan() {
+        write!(f, "NaN")
+    } else if n.is_infinite() {
+        write!(f, "{}", if n > 0.0 { "Infinity" } else { "-Infinity" })
+    } else if n != 0.0 && !(1e-6..1e21).contains(&n.abs()) {
+        write!(f, "{:e}", n)
+    } else {
+        write!(f, "{}", n)
+    }
+}
+
+impl std::ops::Neg for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn neg(self) -> Self::Output {
+        match self {
+            Self::Number(n) => Ok(Self::Number(-n)),
+            _ => Err("Operand must be a number."),
+        }
+    }
+}
+
+impl std::ops::Add for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn add(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number(x + y)),
+            (Self::String(x), Self::String(y)) => Ok(Self::String(format!("{}{}", x, y).into())),
+            _ => Err("Operands must be two numbers or two strings."),
+        }
+    }
+}
+
+impl std::ops::Sub for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn sub(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number(x - y)),
+            _ => Err("Operands must be numbers."),
+        }
+    }
+}
+
+impl std::ops::Mul for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn mul(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number(x * y)),
+            _ => Err("Operands must be numbers."),
+        }
+    }
+}
+
+impl std::ops::Div for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn div(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number(x / y)),
+            _ => Err("Operands must be numbers."),
+        }
+    }
+}
+
+/// Floored modulo: the result takes the sign of the divisor, so `-7 % 3` is
+/// `2`, and `a == (a ~/ b) * b + a % b` always holds
+impl std::ops::Rem for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn rem(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number(x - y * (x / y).floor())),
+            _ => Err("Operands must be numbers."),
+        }
+    }
+}
+
+impl Value {
+    /// `x ** y`
+    pub fn pow(self, other: Self) -> Result<Self, &'static str> {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number(x.powf(y))),
+            _ => Err("Operands must be numbers."),
+        }
+    }
+
+    /// `x ~/ y`, division rounded down to a whole number
+    pub fn int_div(self, other: Self) -> Result<Self, &'static str> {
+        match (self, other) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Number((x / y).floor())),
+            _ => Err("Operands must be numbers."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_display() {
+        let cases = [
+            (3.0, "3"),
+            (-0.25, "-0.25"),
+            (1e20, "100000000000000000000"),
+            (1e21, "1e21"),
+            (-1e300, "-1e300"),
+            (0.000001, "0.000001"),
+            (1.5e-7, "1.5e-7"),
+            (f64::INFINITY, "Infinity"),
+            (f64::NEG_INFINITY, "-Infinity"),
+            (f64::NAN, "NaN"),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(Value::Number(n).to_string(), expected);
+        }
+    }
+
+    #[rustfmt::skip]
+    #[test]
+    fn floored_operators() {
+        let n = Value::Number;
+        let cases = [
+            (n(-7.0) % n(3.0), "2"),
+            (n(7.0) % n(-3.0), "-2"),
+            (n(7.0) % n(3.0), "1"),
+            (n(-7.0) % n(-3.0), "-1"),
+            (n(5.5) % n(2.0), "1.5"),
+            (n(7.0) % n(0.0), "NaN"),
+            (n(-7.0).int_div(n(2.0)), "-4"),
+            (n(7.0).int_div(n(-2.0)), "-4"),
+            (n(7.0).int_div(n(2.0)), "3"),
+            (n(-6.0).int_div(n(3.0)), "-2"),
+            (n(7.0).int_div(n(0.0)), "Infinity"),
+            (n(-7.0).int_div(n(0.0)), "-Infinity"),
+            (n(0.0).int_div(n(0.0)), "NaN"),
+            (n(2.0).pow(n(10.0)), "1024"),
+        ];
+        for (result, expected) in cases {
+            assert_eq!(result.unwrap().to_string(), expected);
+        }
+    }
+
+    #[rustfmt::skip]
+    #[test]
+    fn operand_errors() {
+        let s = |s: &str| Value::String(s.into());
+        let cases = [
+            (Value::Number(1.0) % s("a"), "Operands must be numbers."),
+            (Value::Boolean(true) % Value::Number(2.0), "Operands must be numbers."),
+            (Value::Number(1.0).int_div(Value::Nil), "Operands must be numbers."),
+            (s("a").int_div(s("b")), "Operands must be numbers."),
+            (Value::Number(2.0).pow(s("3")), "Operands must be numbers."),
+            (Value::Nil.pow(Value::Number(2.0)), "Operands must be numbers."),
+            (s("a") - Value::Number(1.0), "Operands must be numbers."),
+            (Value::Number(1.0) + Value::Boolean(false), "Operands must be two numbers or two strings."),
+            (-s("a"), "Operand must be a number."),
+        ];
+        for (result, expected) in cases {
+            assert_eq!(result.unwrap_err(), expected);
+        }
+    }
+}
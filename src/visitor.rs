@@ -0,0 +1,941 @@
+use crate::{
+    parser::{Expr, ExprId, FunctionDecl, Stmt},
+    token::{Literal, Token},
+};
+use std::convert::Infallible;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+/// The result of visiting a node. The default `walk_*` traversals use it to
+/// stop at the first child that fails, handing the failure back up, and to
+/// conjure up a result for nodes that have nothing to report.
+pub trait VisitorResult {
+    /// What a failed visit carries back up, like the `E` of a `Result`
+    type Residual;
+
+    fn output() -> Self;
+    fn branch(self) -> ControlFlow<Self::Residual>;
+    fn from_residual(residual: Self::Residual) -> Self;
+}
+
+impl VisitorResult for () {
+    type Residual = Infallible;
+
+    fn output() -> Self {}
+
+    fn branch(self) -> ControlFlow<Self::Residual> {
+        ControlFlow::Continue(())
+    }
+
+    fn from_residual(residual: Self::Residual) -> Self {
+        match residual {}
+    }
+}
+
+impl<T: Default, E> VisitorResult for Result<T, E> {
+    type Residual = E;
+
+    fn output() -> Self {
+        Ok(T::default())
+    }
+
+    fn branch(self) -> ControlFlow<Self::Residual> {
+        match self {
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+
+    fn from_residual(residual: Self::Residual) -> Self {
+        Err(residual)
+    }
+}
+
+/// Visits a child node, returning early from the enclosing `walk_*` if the
+/// visit failed
+macro_rules! try_visit {
+    ($visit:expr) => {
+        if let ControlFlow::Break(residual) = VisitorResult::branch($visit) {
+            return VisitorResult::from_residual(residual);
+        }
+    };
+}
+
+/// Visits expressions and statements by reference. Every method defaults to
+/// walking the node's children, stopping at the first one that fails, so a
+/// visitor only needs to override the node kinds it cares about.
+pub trait Visitor<R: VisitorResult> {
+    fn visit_binary_expr(&mut self, left: &Expr, operator: &Token, right: &Expr) -> R {
+        walk_binary_expr(self, left, operator, right)
+    }
+
+    fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> R {
+        walk_unary_expr(self, operator, right)
+    }
+
+    fn visit_literal_expr(&mut self, _value: &Literal) -> R {
+        R::output()
+    }
+
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> R {
+        walk_grouping_expr(self, expression)
+    }
+
+    fn visit_variable_expr(&mut self, _id: ExprId, _name: &Token) -> R {
+        R::output()
+    }
+
+    fn visit_assign_expr(&mut self, id: ExprId, name: &Token, value: &Expr) -> R {
+        walk_assign_expr(self, id, name, value)
+    }
+
+    fn visit_logical_expr(&mut self, left: &Expr, operator: &Token, right: &Expr) -> R {
+        walk_logical_expr(self, left, operator, right)
+    }
+
+    fn visit_call_expr(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> R {
+        walk_call_expr(self, callee, paren, arguments)
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> R {
+        walk_get_expr(self, object, name)
+    }
+
+    fn visit_set_expr(&mut self, object: &Expr, name: &Token, value: &Expr) -> R {
+        walk_set_expr(self, object, name, value)
+    }
+
+    fn visit_this_expr(&mut self, _id: ExprId, _keyword: &Token) -> R {
+        R::output()
+    }
+
+    fn visit_super_expr(&mut self, _id: ExprId, _keyword: &Token, _method: &Token) -> R {
+        R::output()
+    }
+
+    fn visit_interpolation_expr(&mut self, parts: &[Expr]) -> R {
+        walk_interpolation_expr(self, parts)
+    }
+
+    fn visit_ternary_expr(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Expr,
+        else_branch: &Expr,
+    ) -> R {
+        walk_ternary_expr(self, condition, then_branch, else_branch)
+    }
+
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> R {
+        walk_block_stmt(self, statements)
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+        doc: Option<&str>,
+    ) -> R {
+        walk_class_stmt(self, name, superclass, methods, doc)
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> R {
+        walk_expression_stmt(self, expression)
+    }
+
+    fn visit_function_stmt(&mut self, declaration: &Rc<FunctionDecl>) -> R {
+        walk_function_stmt(self, declaration)
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> R {
+        walk_if_stmt(self, condition, then_branch, else_branch)
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) -> R {
+        walk_print_stmt(self, expression)
+    }
+
+    fn visit_return_stmt(&mut self, keyword: &Token, value: Option<&Expr>) -> R {
+        walk_return_stmt(self, keyword, value)
+    }
+
+    fn visit_var_stmt(&mut self, name: &Token, initializer: Option<&Expr>, doc: Option<&str>) -> R {
+        walk_var_stmt(self, name, initializer, doc)
+    }
+
+    fn visit_while_stmt(&mut self, condition: &Expr, body: &Stmt) -> R {
+        walk_while_stmt(self, condition, body)
+    }
+}
+
+pub fn walk_binary_expr<R, V>(visitor: &mut V, left: &Expr, _operator: &Token, right: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(left.accept(visitor));
+    try_visit!(right.accept(visitor));
+    R::output()
+}
+
+pub fn walk_unary_expr<R, V>(visitor: &mut V, _operator: &Token, right: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(right.accept(visitor));
+    R::output()
+}
+
+pub fn walk_grouping_expr<R, V>(visitor: &mut V, expression: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(expression.accept(visitor));
+    R::output()
+}
+
+pub fn walk_assign_expr<R, V>(visitor: &mut V, _id: ExprId, _name: &Token, value: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(value.accept(visitor));
+    R::output()
+}
+
+pub fn walk_logical_expr<R, V>(visitor: &mut V, left: &Expr, _operator: &Token, right: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(left.accept(visitor));
+    try_visit!(right.accept(visitor));
+    R::output()
+}
+
+pub fn walk_call_expr<R, V>(visitor: &mut V, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(callee.accept(visitor));
+    for argument in arguments {
+        try_visit!(argument.accept(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_get_expr<R, V>(visitor: &mut V, object: &Expr, _name: &Token) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(object.accept(visitor));
+    R::output()
+}
+
+pub fn walk_set_expr<R, V>(visitor: &mut V, object: &Expr, _name: &Token, value: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(object.accept(visitor));
+    try_visit!(value.accept(visitor));
+    R::output()
+}
+
+pub fn walk_interpolation_expr<R, V>(visitor: &mut V, parts: &[Expr]) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    for part in parts {
+        try_visit!(part.accept(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_ternary_expr<R, V>(
+    visitor: &mut V,
+    condition: &Expr,
+    then_branch: &Expr,
+    else_branch: &Expr,
+) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(condition.accept(visitor));
+    try_visit!(then_branch.accept(visitor));
+    try_visit!(else_branch.accept(visitor));
+    R::output()
+}
+
+pub fn walk_block_stmt<R, V>(visitor: &mut V, statements: &[Stmt]) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    for statement in statements {
+        try_visit!(statement.accept(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_class_stmt<R, V>(
+    visitor: &mut V,
+    _name: &Token,
+    superclass: Option<&Expr>,
+    methods: &[Rc<FunctionDecl>],
+    _doc: Option<&str>,
+) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    if let Some(superclass) = superclass {
+        try_visit!(superclass.accept(visitor));
+    }
+    for method in methods {
+        try_visit!(walk_function_stmt(visitor, method));
+    }
+    R::output()
+}
+
+pub fn walk_expression_stmt<R, V>(visitor: &mut V, expression: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(expression.accept(visitor));
+    R::output()
+}
+
+pub fn walk_function_stmt<R, V>(visitor: &mut V, declaration: &FunctionDecl) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    walk_block_stmt(visitor, &declaration.body)
+}
+
+pub fn walk_if_stmt<R, V>(
+    visitor: &mut V,
+    condition: &Expr,
+    then_branch: &Stmt,
+    else_branch: Option<&Stmt>,
+) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(condition.accept(visitor));
+    try_visit!(then_branch.accept(visitor));
+    if let Some(else_branch) = else_branch {
+        try_visit!(else_branch.accept(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_print_stmt<R, V>(visitor: &mut V, expression: &Expr) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(expression.accept(visitor));
+    R::output()
+}
+
+pub fn walk_return_stmt<R, V>(visitor: &mut V, _keyword: &Token, value: Option<&Expr>) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    if let Some(value) = value {
+        try_visit!(value.accept(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_var_stmt<R, V>(
+    visitor: &mut V,
+    _name: &Token,
+    initializer: Option<&Expr>,
+    _doc: Option<&str>,
+) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    if let Some(initializer) = initializer {
+        try_visit!(initializer.accept(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_while_stmt<R, V>(visitor: &mut V, condition: &Expr, body: &Stmt) -> R
+where
+    R: VisitorResult,
+    V: Visitor<R> + ?Sized,
+{
+    try_visit!(condition.accept(visitor));
+    try_visit!(body.accept(visitor));
+    R::output()
+}
+
+/// Like [`Visitor`], but with mutable access to the tree so that passes such
+/// as optimisers can rewrite it in place. Function bodies are shared behind
+/// an `Rc`, so rewriting one clones it if it's still shared.
+pub trait VisitorMut<R: VisitorResult> {
+    /// Visits any expression. The `walk_*_mut` functions visit each child
+    /// through here, so overriding it lets a pass replace whole nodes, for
+    /// instance folding `1 + 2` into `3` after walking the children.
+    fn visit_expr_mut(&mut self, expr: &mut Expr) -> R {
+        expr.accept_mut(self)
+    }
+
+    fn visit_binary_expr_mut(
+        &mut self,
+        left: &mut Expr,
+        operator: &mut Token,
+        right: &mut Expr,
+    ) -> R {
+        walk_binary_expr_mut(self, left, operator, right)
+    }
+
+    fn visit_unary_expr_mut(&mut self, operator: &mut Token, right: &mut Expr) -> R {
+        walk_unary_expr_mut(self, operator, right)
+    }
+
+    fn visit_literal_expr_mut(&mut self, _value: &mut Literal) -> R {
+        R::output()
+    }
+
+    fn visit_grouping_expr_mut(&mut self, expression: &mut Expr) -> R {
+        walk_grouping_expr_mut(self, expression)
+    }
+
+    fn visit_variable_expr_mut(&mut self, _id: ExprId, _name: &mut Token) -> R {
+        R::output()
+    }
+
+    fn visit_assign_expr_mut(&mut self, id: ExprId, name: &mut Token, value: &mut Expr) -> R {
+        walk_assign_expr_mut(self, id, name, value)
+    }
+
+    fn visit_logical_expr_mut(
+        &mut self,
+        left: &mut Expr,
+        operator: &mut Token,
+        right: &mut Expr,
+    ) -> R {
+        walk_logical_expr_mut(self, left, operator, right)
+    }
+
+    fn visit_call_expr_mut(
+        &mut self,
+        callee: &mut Expr,
+        paren: &mut Token,
+        arguments: &mut [Expr],
+    ) -> R {
+        walk_call_expr_mut(self, callee, paren, arguments)
+    }
+
+    fn visit_get_expr_mut(&mut self, object: &mut Expr, name: &mut Token) -> R {
+        walk_get_expr_mut(self, object, name)
+    }
+
+    fn visit_set_expr_mut(&mut self, object: &mut Expr, name: &mut Token, value: &mut Expr) -> R {
+        walk_set_expr_mut(self, object, name, value)
+    }
+
+    fn visit_this_expr_mut(&mut self, _id: ExprId, _keyword: &mut Token) -> R {
+        R::output()
+    }
+
+    fn visit_super_expr_mut(
+        &mut self,
+        _id: ExprId,
+        _keyword: &mut Token,
+        _method: &mut Token,
+    ) -> R {
+        R::output()
+    }
+
+    fn visit_interpolation_expr_mut(&mut self, parts: &mut [Expr]) -> R {
+        walk_interpolation_expr_mut(self, parts)
+    }
+
+    fn visit_ternary_expr_mut(
+        &mut self,
+        condition: &mut Expr,
+        then_branch: &mut Expr,
+        else_branch: &mut Expr,
+    ) -> R {
+        walk_ternary_expr_mut(self, condition, then_branch, else_branch)
+    }
+
+    fn visit_block_stmt_mut(&mut self, statements: &mut [Stmt]) -> R {
+        walk_block_stmt_mut(self, statements)
+    }
+
+    fn visit_class_stmt_mut(
+        &mut self,
+        name: &mut Token,
+        superclass: Option<&mut Expr>,
+        methods: &mut [Rc<FunctionDecl>],
+        doc: &mut Option<Rc<String>>,
+    ) -> R {
+        walk_class_stmt_mut(self, name, superclass, methods, doc)
+    }
+
+    fn visit_expression_stmt_mut(&mut self, expression: &mut Expr) -> R {
+        walk_expression_stmt_mut(self, expression)
+    }
+
+    fn visit_function_stmt_mut(&mut self, declaration: &mut Rc<FunctionDecl>) -> R {
+        walk_function_stmt_mut(self, declaration)
+    }
+
+    fn visit_if_stmt_mut(
+        &mut self,
+        condition: &mut Expr,
+        then_branch: &mut Stmt,
+        else_branch: Option<&mut Stmt>,
+    ) -> R {
+        walk_if_stmt_mut(self, condition, then_branch, else_branch)
+    }
+
+    fn visit_print_stmt_mut(&mut self, expression: &mut Expr) -> R {
+        walk_print_stmt_mut(self, expression)
+    }
+
+    fn visit_return_stmt_mut(&mut self, keyword: &mut Token, value: Option<&mut Expr>) -> R {
+        walk_return_stmt_mut(self, keyword, value)
+    }
+
+    fn visit_var_stmt_mut(
+        &mut self,
+        name: &mut Token,
+        initializer: Option<&mut Expr>,
+        doc: &mut Option<Rc<String>>,
+    ) -> R {
+        walk_var_stmt_mut(self, name, initializer, doc)
+    }
+
+    fn visit_while_stmt_mut(&mut self, condition: &mut Expr, body: &mut Stmt) -> R {
+        walk_while_stmt_mut(self, condition, body)
+    }
+}
+
+pub fn walk_binary_expr_mut<R, V>(
+    visitor: &mut V,
+    left: &mut Expr,
+    _operator: &mut Token,
+    right: &mut Expr,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(left));
+    try_visit!(visitor.visit_expr_mut(right));
+    R::output()
+}
+
+pub fn walk_unary_expr_mut<R, V>(visitor: &mut V, _operator: &mut Token, right: &mut Expr) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(right));
+    R::output()
+}
+
+pub fn walk_grouping_expr_mut<R, V>(visitor: &mut V, expression: &mut Expr) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(expression));
+    R::output()
+}
+
+pub fn walk_assign_expr_mut<R, V>(
+    visitor: &mut V,
+    _id: ExprId,
+    _name: &mut Token,
+    value: &mut Expr,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(value));
+    R::output()
+}
+
+pub fn walk_logical_expr_mut<R, V>(
+    visitor: &mut V,
+    left: &mut Expr,
+    _operator: &mut Token,
+    right: &mut Expr,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(left));
+    try_visit!(visitor.visit_expr_mut(right));
+    R::output()
+}
+
+pub fn walk_call_expr_mut<R, V>(
+    visitor: &mut V,
+    callee: &mut Expr,
+    _paren: &mut Token,
+    arguments: &mut [Expr],
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(callee));
+    for argument in arguments {
+        try_visit!(visitor.visit_expr_mut(argument));
+    }
+    R::output()
+}
+
+pub fn walk_get_expr_mut<R, V>(visitor: &mut V, object: &mut Expr, _name: &mut Token) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(object));
+    R::output()
+}
+
+pub fn walk_set_expr_mut<R, V>(
+    visitor: &mut V,
+    object: &mut Expr,
+    _name: &mut Token,
+    value: &mut Expr,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(object));
+    try_visit!(visitor.visit_expr_mut(value));
+    R::output()
+}
+
+pub fn walk_interpolation_expr_mut<R, V>(visitor: &mut V, parts: &mut [Expr]) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    for part in parts {
+        try_visit!(visitor.visit_expr_mut(part));
+    }
+    R::output()
+}
+
+pub fn walk_ternary_expr_mut<R, V>(
+    visitor: &mut V,
+    condition: &mut Expr,
+    then_branch: &mut Expr,
+    else_branch: &mut Expr,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(condition));
+    try_visit!(visitor.visit_expr_mut(then_branch));
+    try_visit!(visitor.visit_expr_mut(else_branch));
+    R::output()
+}
+
+pub fn walk_block_stmt_mut<R, V>(visitor: &mut V, statements: &mut [Stmt]) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    for statement in statements {
+        try_visit!(statement.accept_mut(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_class_stmt_mut<R, V>(
+    visitor: &mut V,
+    _name: &mut Token,
+    superclass: Option<&mut Expr>,
+    methods: &mut [Rc<FunctionDecl>],
+    _doc: &mut Option<Rc<String>>,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    if let Some(superclass) = superclass {
+        try_visit!(visitor.visit_expr_mut(superclass));
+    }
+    for method in methods {
+        try_visit!(walk_function_stmt_mut(visitor, method));
+    }
+    R::output()
+}
+
+pub fn walk_expression_stmt_mut<R, V>(visitor: &mut V, expression: &mut Expr) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(expression));
+    R::output()
+}
+
+pub fn walk_function_stmt_mut<R, V>(visitor: &mut V, declaration: &mut Rc<FunctionDecl>) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    walk_block_stmt_mut(visitor, &mut Rc::make_mut(declaration).body)
+}
+
+pub fn walk_if_stmt_mut<R, V>(
+    visitor: &mut V,
+    condition: &mut Expr,
+    then_branch: &mut Stmt,
+    else_branch: Option<&mut Stmt>,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(condition));
+    try_visit!(then_branch.accept_mut(visitor));
+    if let Some(else_branch) = else_branch {
+        try_visit!(else_branch.accept_mut(visitor));
+    }
+    R::output()
+}
+
+pub fn walk_print_stmt_mut<R, V>(visitor: &mut V, expression: &mut Expr) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(expression));
+    R::output()
+}
+
+pub fn walk_return_stmt_mut<R, V>(
+    visitor: &mut V,
+    _keyword: &mut Token,
+    value: Option<&mut Expr>,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    if let Some(value) = value {
+        try_visit!(visitor.visit_expr_mut(value));
+    }
+    R::output()
+}
+
+pub fn walk_var_stmt_mut<R, V>(
+    visitor: &mut V,
+    _name: &mut Token,
+    initializer: Option<&mut Expr>,
+    _doc: &mut Option<Rc<String>>,
+) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    if let Some(initializer) = initializer {
+        try_visit!(visitor.visit_expr_mut(initializer));
+    }
+    R::output()
+}
+
+pub fn walk_while_stmt_mut<R, V>(visitor: &mut V, condition: &mut Expr, body: &mut Stmt) -> R
+where
+    R: VisitorResult,
+    V: VisitorMut<R> + ?Sized,
+{
+    try_visit!(visitor.visit_expr_mut(condition));
+    try_visit!(body.accept_mut(visitor));
+    R::output()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner, token::TokenType};
+
+    /// Rejects any use of a variable called `bad`, leaving the rest of the
+    /// traversal to the defaults
+    struct Linter;
+
+    impl Visitor<Result<(), String>> for Linter {
+        fn visit_variable_expr(&mut self, _id: ExprId, name: &Token) -> Result<(), String> {
+            match name.lexeme.as_str() {
+                "bad" => Err(format!("'bad' used on line {}", name.line)),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn lint(source: &str) -> Result<(), String> {
+        let (tokens, errors) = Scanner::new(source.into()).scan_tokens();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|_| "parse error")
+            .unwrap();
+        statements
+            .iter()
+            .try_for_each(|stmt| stmt.accept(&mut Linter))
+    }
+
+    #[test]
+    fn walks_propagate_child_errors() {
+        assert_eq!(lint("print good + 1;"), Ok(()));
+        for source in [
+            "print 1 + -bad;",
+            "print f(1, (bad));",
+            "print \"${bad}\";",
+            "class A { m() { if (true) { return bad.x; } } }",
+            "var x = true ? 1 : bad;",
+        ] {
+            assert_eq!(
+                lint(source),
+                Err("'bad' used on line 1".into()),
+                "{}",
+                source
+            );
+        }
+    }
+
+    /// Collects the docs of every class and variable declaration
+    #[derive(Default)]
+    struct Docs(Vec<String>);
+
+    impl Visitor<()> for Docs {
+        fn visit_class_stmt(
+            &mut self,
+            name: &Token,
+            superclass: Option<&Expr>,
+            methods: &[Rc<FunctionDecl>],
+            doc: Option<&str>,
+        ) {
+            self.0.push(format!("{}: {:?}", name.lexeme, doc));
+            walk_class_stmt(self, name, superclass, methods, doc)
+        }
+
+        fn visit_var_stmt(&mut self, name: &Token, initializer: Option<&Expr>, doc: Option<&str>) {
+            self.0.push(format!("{}: {:?}", name.lexeme, doc));
+            walk_var_stmt(self, name, initializer, doc)
+        }
+    }
+
+    #[test]
+    fn declarations_pass_their_docs() {
+        let source = "/// A point\nclass P {}\n/// The origin\nvar o = P();\nvar x;";
+        let (tokens, _) = Scanner::new(source.into()).scan_tokens();
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|_| "parse error")
+            .unwrap();
+        let mut docs = Docs::default();
+        for stmt in &statements {
+            stmt.accept(&mut docs);
+        }
+        assert_eq!(
+            docs.0,
+            [
+                r#"P: Some("A point")"#,
+                r#"o: Some("The origin")"#,
+                "x: None"
+            ]
+        );
+    }
+
+    /// Folds arithmetic on number literals into a single literal
+    struct Folder;
+
+    impl VisitorMut<()> for Folder {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            expr.accept_mut(self);
+            let Expr::Binary {
+                left,
+                operator,
+                right,
+            } = expr
+            else {
+                return;
+            };
+            let (
+                Expr::Literal {
+                    value: Literal::Number(a),
+                    ..
+                },
+                Expr::Literal {
+                    value: Literal::Number(b),
+                    ..
+                },
+            ) = (&**left, &**right)
+            else {
+                return;
+            };
+            let value = match operator.token_type {
+                TokenType::Plus => a + b,
+                TokenType::Star => a * b,
+                _ => return,
+            };
+            *expr = Expr::Literal {
+                value: Literal::Number(value),
+                span: expr.span(),
+            };
+        }
+    }
+
+    #[test]
+    fn visit_expr_mut_rewrites_nodes() {
+        let (tokens, _) =
+            Scanner::new("print x - 1 + 2 * 3; print 1 + 2 * 3;".into()).scan_tokens();
+        let mut statements = Parser::new(tokens)
+            .parse()
+            .map_err(|_| "parse error")
+            .unwrap();
+        let mut printed = Vec::new();
+        for stmt in &mut statements {
+            let Stmt::Print(expr) = stmt else {
+                unreachable!()
+            };
+            Folder.visit_expr_mut(expr);
+            printed.push(match expr {
+                Expr::Literal { value, .. } => value.to_string(),
+                Expr::Binary { right, .. } => match &**right {
+                    Expr::Literal { value, .. } => format!("(x - 1) + {}", value),
+                    _ => "not folded".into(),
+                },
+                _ => "not folded".into(),
+            });
+        }
+        assert_eq!(printed, ["(x - 1) + 6", "7"]);
+    }
+}